@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::error::SimpleErrorHandler;
+use crate::parser::{expr, stmt};
+use crate::scanner::token::{Literal, Token};
+use crate::scanner::token_type::TokenType;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Type {
+    Any,
+    Number,
+    String,
+    Bool,
+    Range,
+    Nil,
+}
+
+impl Type {
+    fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "Any" => Some(Type::Any),
+            "Number" => Some(Type::Number),
+            "String" => Some(Type::String),
+            "Bool" => Some(Type::Bool),
+            "Range" => Some(Type::Range),
+            "Nil" => Some(Type::Nil),
+            _ => None,
+        }
+    }
+
+    /// Unannotated values are `Any`, which is compatible with every type in both directions
+    fn accepts(self, other: Type) -> bool {
+        self == Type::Any || other == Type::Any || self == other
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Number => write!(f, "Number"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Range => write!(f, "Range"),
+            Type::Nil => write!(f, "Nil"),
+        }
+    }
+}
+
+/// Opt-in static pass that checks values against `var` type annotations.
+/// Errors are reported through the error handler; the AST is left untouched.
+pub struct Checker<'a> {
+    error_handler: &'a mut SimpleErrorHandler,
+    scopes: Vec<HashMap<String, Type>>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(error_handler: &'a mut SimpleErrorHandler) -> Self {
+        Self {
+            error_handler,
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn check(&mut self, statements: &[stmt::Stmt]) {
+        for statement in statements {
+            self.check_stmt(statement);
+        }
+    }
+
+    fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Type {
+        let value = self.check_expr(&expr.value);
+        let declared = self.lookup(&expr.name.lexeme);
+        if !declared.accepts(value) {
+            self.error_at(
+                &expr.value,
+                &expr.name,
+                &format!(
+                    "Cannot assign a value of type {} to '{}' of type {}.",
+                    value, expr.name.lexeme, declared
+                ),
+            );
+        }
+        value
+    }
+
+    fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Type {
+        let left = self.check_expr(&expr.left);
+        let right = self.check_expr(&expr.right);
+
+        match expr.operator.token_type {
+            TokenType::BangEqual | TokenType::EqualEqual | TokenType::Is => Type::Bool,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                self.expect_numbers(&expr.operator, left, right);
+                Type::Bool
+            }
+            TokenType::Minus | TokenType::Slash | TokenType::Star => {
+                self.expect_numbers(&expr.operator, left, right);
+                Type::Number
+            }
+            TokenType::Plus => match (left, right) {
+                (Type::Number, Type::Number) | (Type::String, Type::String) => left,
+                (Type::Any, Type::Any) => Type::Any,
+                (Type::Any, known @ (Type::Number | Type::String))
+                | (known @ (Type::Number | Type::String), Type::Any) => known,
+                _ => {
+                    self.error(
+                        &expr.operator,
+                        &format!(
+                            "Operands of '+' must be two numbers or two strings, found {} and {}.",
+                            left, right
+                        ),
+                    );
+                    Type::Any
+                }
+            },
+            _ => Type::Any,
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &expr::Call) -> Type {
+        self.check_expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.check_expr(argument);
+        }
+        Type::Any
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) -> Type {
+        self.check_expr(&expr.object);
+        Type::Any
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &expr::Grouping) -> Type {
+        self.check_expr(&expr.expression)
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Type {
+        let object = self.check_expr(&expr.object);
+        let index = self.check_expr(&expr.index);
+        if !Type::String.accepts(object) {
+            self.error(
+                &expr.bracket,
+                &format!("Only strings can be indexed, found {}.", object),
+            );
+        }
+        if !Type::Range.accepts(index) && !Type::Number.accepts(index) {
+            self.error(
+                &expr.bracket,
+                &format!("String index must be a Number or a Range, found {}.", index),
+            );
+        }
+        Type::String
+    }
+
+    fn visit_literal_expr(&self, expr: &expr::Literal) -> Type {
+        match &*expr.value {
+            Literal::Number(_) => Type::Number,
+            Literal::String(_) => Type::String,
+            Literal::True | Literal::False => Type::Bool,
+            Literal::Nil => Type::Nil,
+        }
+    }
+
+    fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Type {
+        let left = self.check_expr(&expr.left);
+        let right = self.check_expr(&expr.right);
+        if left == right {
+            left
+        } else {
+            Type::Any
+        }
+    }
+
+    fn visit_range_expr(&mut self, expr: &expr::Range) -> Type {
+        let start = self.check_expr(&expr.start);
+        let end = self.check_expr(&expr.end);
+        if !Type::Number.accepts(start) || !Type::Number.accepts(end) {
+            self.error(
+                &expr.operator,
+                &format!("Range bounds must be Numbers, found {} and {}.", start, end),
+            );
+        }
+        Type::Range
+    }
+
+    fn visit_unary_expr(&mut self, expr: &expr::Unary) -> Type {
+        let right = self.check_expr(&expr.right);
+        match expr.operator.token_type {
+            TokenType::Bang => Type::Bool,
+            _ => {
+                if !Type::Number.accepts(right) {
+                    self.error(
+                        &expr.operator,
+                        &format!("Operand must be a Number, found {}.", right),
+                    );
+                }
+                Type::Number
+            }
+        }
+    }
+
+    fn visit_variable_expr(&self, expr: &expr::Variable) -> Type {
+        self.lookup(&expr.name.lexeme)
+    }
+
+    fn check_expr(&mut self, expr: &expr::Expr) -> Type {
+        match expr {
+            expr::Expr::Assign(assign) => self.visit_assign_expr(assign),
+            expr::Expr::Binary(binary) => self.visit_binary_expr(binary),
+            expr::Expr::Call(call) => self.visit_call_expr(call),
+            expr::Expr::Error(_) => Type::Any,
+            expr::Expr::Get(get) => self.visit_get_expr(get),
+            expr::Expr::Grouping(grouping) => self.visit_grouping_expr(grouping),
+            expr::Expr::Index(index) => self.visit_index_expr(index),
+            expr::Expr::Literal(literal) => self.visit_literal_expr(literal),
+            expr::Expr::Logical(logical) => self.visit_logical_expr(logical),
+            expr::Expr::Range(range) => self.visit_range_expr(range),
+            expr::Expr::Unary(unary) => self.visit_unary_expr(unary),
+            expr::Expr::Variable(variable) => self.visit_variable_expr(variable),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &stmt::Block) {
+        self.scopes.push(HashMap::new());
+        self.check(&stmt.statements);
+        self.scopes.pop();
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &stmt::ForIn) {
+        let iterable = self.check_expr(&stmt.iterable);
+        if !Type::Range.accepts(iterable) {
+            self.error(
+                &stmt.name,
+                &format!("Can only iterate over a Range, found {}.", iterable),
+            );
+        }
+        let mut scope = HashMap::new();
+        scope.insert(stmt.name.lexeme.clone(), Type::Number);
+        self.scopes.push(scope);
+        self.check_stmt(&stmt.body);
+        self.scopes.pop();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &stmt::If) {
+        self.check_expr(&stmt.condition);
+        self.check_stmt(&stmt.then_branch);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.check_stmt(else_branch);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &stmt::Var) {
+        let value = self.check_expr(&stmt.initializer);
+        let declared = match &stmt.type_annotation {
+            Some(annotation) => match Type::from_name(&annotation.lexeme) {
+                Some(declared) => declared,
+                None => {
+                    self.error(
+                        annotation,
+                        &format!("Unknown type '{}'.", annotation.lexeme),
+                    );
+                    Type::Any
+                }
+            },
+            None => Type::Any,
+        };
+        // `var x: Number;` declares `x` without initializing it, so the implicit `nil`
+        // the parser supplies isn't held against the annotation
+        let implicit = stmt.initializer.span().is_none();
+        if !implicit && !declared.accepts(value) {
+            self.error_at(
+                &stmt.initializer,
+                &stmt.name,
+                &format!(
+                    "Cannot initialize '{}' of type {} with a value of type {}.",
+                    stmt.name.lexeme, declared, value
+                ),
+            );
+        }
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(stmt.name.lexeme.clone(), declared);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &stmt::While) {
+        self.check_expr(&stmt.condition);
+        self.check_stmt(&stmt.body);
+    }
+
+    fn check_stmt(&mut self, statement: &stmt::Stmt) {
+        match statement {
+            stmt::Stmt::Assert(assert_statement) => {
+                self.check_expr(&assert_statement.condition);
+                if let Some(message) = &assert_statement.message {
+                    self.check_expr(message);
+                }
+            }
+            stmt::Stmt::Block(block_statement) => self.visit_block_stmt(block_statement),
+            stmt::Stmt::Enum(enum_statement) => {
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert(enum_statement.name.lexeme.clone(), Type::Any);
+            }
+            stmt::Stmt::Error(_) => (),
+            stmt::Stmt::Expression(expression_statement) => {
+                self.check_expr(&expression_statement.expression);
+            }
+            stmt::Stmt::ForIn(for_in_statement) => self.visit_for_in_stmt(for_in_statement),
+            stmt::Stmt::If(if_statement) => self.visit_if_stmt(if_statement),
+            stmt::Stmt::Print(print_statement) => {
+                self.check_expr(&print_statement.expression);
+            }
+            stmt::Stmt::Var(var_statement) => self.visit_var_stmt(var_statement),
+            stmt::Stmt::While(while_statement) => self.visit_while_stmt(while_statement),
+        }
+    }
+
+    /// Names the checker hasn't seen (e.g. globals from an earlier REPL line) are `Any`
+    fn lookup(&self, name: &str) -> Type {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .unwrap_or(Type::Any)
+    }
+
+    fn expect_numbers(&mut self, operator: &Token, left: Type, right: Type) {
+        if !Type::Number.accepts(left) || !Type::Number.accepts(right) {
+            self.error(
+                operator,
+                &format!("Operands must be Numbers, found {} and {}.", left, right),
+            );
+        }
+    }
+
+    /// Reports against the whole of `expr`, or `fallback` if the expression has no source
+    fn error_at(&mut self, expr: &expr::Expr, fallback: &Token, msg: &str) {
+        match expr.span() {
+            Some(span) => self.error_handler.type_error_at(span, msg),
+            None => self.error(fallback, msg),
+        }
+    }
+
+    fn error(&mut self, token: &Token, msg: &str) {
+        self.error_handler.type_error(token, msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::Checker;
+    use crate::diagnostic::{Diagnostic, DiagnosticSink};
+    use crate::error::SimpleErrorHandler;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    struct Collector(Rc<RefCell<Vec<Diagnostic>>>);
+
+    impl DiagnosticSink for Collector {
+        fn report(&mut self, diagnostic: Diagnostic, _source: &str) {
+            self.0.borrow_mut().push(diagnostic);
+        }
+    }
+
+    /// Parses and checks `source`, returning the messages of any type errors
+    fn check(source: &str) -> Vec<String> {
+        let diagnostics = Rc::new(RefCell::new(Vec::new()));
+        let mut error_handler = SimpleErrorHandler::new();
+        error_handler.set_sink(Box::new(Collector(diagnostics.clone())));
+        error_handler.set_source(source);
+        let tokens = Scanner::new(source, &mut error_handler).scan_tokens();
+        let statements = Parser::new(tokens, &mut error_handler).parse();
+        assert!(!error_handler.had_error, "{:?} doesn't parse", source);
+        Checker::new(&mut error_handler).check(&statements);
+        diagnostics
+            .take()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_matching_annotations() {
+        let errors = check("var n: Number = 1 + 2;\nvar s: String = \"a\";\nvar b: Bool = n > 1;");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_mismatched_initializer() {
+        assert_eq!(
+            check("var n: Number = \"one\";"),
+            vec!["Cannot initialize 'n' of type Number with a value of type String."]
+        );
+    }
+
+    #[test]
+    fn rejects_a_mismatched_assignment() {
+        assert_eq!(
+            check("var n: Number = 1;\nn = true;"),
+            vec!["Cannot assign a value of type Bool to 'n' of type Number."]
+        );
+    }
+
+    #[test]
+    fn checks_assignments_in_nested_scopes() {
+        assert_eq!(check("var n: Number = 1;\n{ n = \"s\"; }").len(), 1);
+        // A local of the same name hides the annotated global
+        assert!(check("var n: Number = 1;\n{ var n = \"s\"; n = \"t\"; }").is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_type_names() {
+        assert_eq!(
+            check("var n: Integer = 1;"),
+            vec!["Unknown type 'Integer'."]
+        );
+    }
+
+    #[test]
+    fn accepts_an_annotation_without_an_initializer() {
+        assert!(check("var n: Number;\nn = 3;").is_empty());
+        assert!(check("var s: String;").is_empty());
+    }
+
+    #[test]
+    fn unannotated_code_has_no_errors() {
+        let errors = check("var a = 1;\na = \"now a string\";\nvar b = a;\nb = nil;\nprint a + b;");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn any_accepts_everything() {
+        assert!(check("var a: Any = 1;\na = \"s\";\nvar n: Number = a;").is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt::Display;
+
+use crate::json;
+use crate::scanner::token::Token;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+impl Severity {
+    /// Lowercase name used in machine-readable output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// Pass of the pipeline that produced a diagnostic
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stage {
+    Scan,
+    Parse,
+    Type,
+    Lint,
+    Format,
+    Runtime,
+}
+
+/// Stable identifiers for each kind of diagnostic, for tools that filter on them
+pub mod codes {
+    pub const SCAN: &str = "scan";
+    pub const PARSE: &str = "parse";
+    pub const TYPE: &str = "type";
+    pub const FORMAT: &str = "format";
+    pub const RUNTIME: &str = "runtime";
+
+    // Warnings from the lint pass
+    pub const UNUSED_VARIABLE: &str = "unused-variable";
+    pub const SHADOWED_VARIABLE: &str = "shadowed-variable";
+    pub const CONSTANT_CONDITION: &str = "constant-condition";
+    pub const SELF_ASSIGNMENT: &str = "self-assignment";
+}
+
+/// Location of a diagnostic. `start` and `end` are char offsets into the source.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Span {
+    /// Extends this span to finish where `other` ends
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..self
+        }
+    }
+}
+
+impl From<&Token> for Span {
+    fn from(token: &Token) -> Self {
+        Self {
+            line: token.line,
+            column: token.column,
+            start: token.start,
+            end: token.end,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub stage: Stage,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    /// Name of the source the span points into, if it isn't the user's own input
+    pub source_name: Option<String>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Human readable position within the line, e.g. " at 'x'" or " at end", taken from
+    /// the source text the span covers. Empty for scanner, formatter and runtime errors,
+    /// and for spans over several lines.
+    pub fn location(&self, source: &str) -> String {
+        if matches!(self.stage, Stage::Scan | Stage::Format | Stage::Runtime) {
+            return String::new();
+        }
+        if self.span.start as usize >= source.chars().count() {
+            return String::from(" at end");
+        }
+        let text: String = source
+            .chars()
+            .skip(self.span.start as usize)
+            .take(self.span.end.saturating_sub(self.span.start) as usize)
+            .collect();
+        if text.is_empty() || text.contains('\n') {
+            String::new()
+        } else {
+            format!(" at '{}'", text)
+        }
+    }
+}
+
+/// Receives every diagnostic produced while scanning, parsing, checking and running.
+/// `source` is the text the diagnostic's span points into.
+pub trait DiagnosticSink {
+    fn report(&mut self, diagnostic: Diagnostic, source: &str);
+}
+
+/// Prints diagnostics to stderr in the classic `[line N] Error at 'x': message` format,
+/// followed by the offending source line with the span underlined
+pub struct StderrSink;
+
+impl DiagnosticSink for StderrSink {
+    fn report(&mut self, diagnostic: Diagnostic, source: &str) {
+        let line = match &diagnostic.source_name {
+            Some(name) => format!("{} line {}", name, diagnostic.span.line),
+            None => format!("line {}", diagnostic.span.line),
+        };
+        if diagnostic.stage == Stage::Runtime {
+            eprintln!("{}", diagnostic.message);
+            eprintln!("[{}]", line);
+        } else {
+            eprintln!(
+                "[{}] {}{}: {}",
+                line,
+                diagnostic.severity,
+                diagnostic.location(source),
+                diagnostic.message
+            );
+        }
+        if let Some(snippet) = render_snippet(diagnostic.span, source) {
+            eprint!("{}", snippet);
+        }
+        for note in &diagnostic.notes {
+            eprintln!("  {}", note);
+        }
+    }
+}
+
+/// Prints each diagnostic to stderr as a single line of JSON, for editors and CI
+pub struct JsonSink {
+    /// File reported for diagnostics from the user's own source
+    file: Option<String>,
+}
+
+impl JsonSink {
+    pub fn new(file: Option<String>) -> Self {
+        Self { file }
+    }
+}
+
+impl DiagnosticSink for JsonSink {
+    fn report(&mut self, diagnostic: Diagnostic, _source: &str) {
+        let file = match diagnostic.source_name.as_ref().or(self.file.as_ref()) {
+            Some(file) => json::quote(file),
+            None => String::from("null"),
+        };
+        eprintln!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"severity\":{},\"code\":{},\"message\":{}}}",
+            file,
+            diagnostic.span.line,
+            diagnostic.span.column,
+            json::quote(diagnostic.severity.as_str()),
+            json::quote(diagnostic.code),
+            json::quote(&diagnostic.message)
+        );
+    }
+}
+
+/// Renders the source line containing the start of `span` with a `^^^` underline,
+/// clipped to the end of that line. Returns `None` if the span is outside `source`.
+pub fn render_snippet(span: Span, source: &str) -> Option<String> {
+    let chars: Vec<char> = source.chars().collect();
+    let start = span.start as usize;
+    if start > chars.len() {
+        return None;
+    }
+    let line_begin = chars[..start]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let line_end = chars[start..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |i| start + i);
+    let line_number = chars[..start].iter().filter(|c| **c == '\n').count() + 1;
+
+    let text: String = chars[line_begin..line_end].iter().collect();
+    // Keep tabs in the padding so the carets line up with the source
+    let padding: String = chars[line_begin..start]
+        .iter()
+        .map(|c| if *c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = (span.end as usize)
+        .min(line_end)
+        .saturating_sub(start)
+        .max(1);
+
+    let gutter = line_number.to_string();
+    let blank = " ".repeat(gutter.len());
+    Some(format!(
+        "{} | {}\n{} | {}{}\n",
+        gutter,
+        text.trim_end_matches('\r'),
+        blank,
+        padding,
+        "^".repeat(width)
+    ))
+}
@@ -1,10 +1,15 @@
+use crate::diagnostic::{codes, Diagnostic, DiagnosticSink, Severity, Span, Stage, StderrSink};
 use crate::interpreter::error::InterpreterError;
 use crate::scanner::token::Token;
-use crate::scanner::token_type::TokenType;
 
 pub struct SimpleErrorHandler {
     pub had_error: bool,
     pub had_runtime_error: bool,
+    /// Name shown in error locations for source that doesn't come from the user, e.g. the prelude
+    pub source_name: Option<String>,
+    sink: Box<dyn DiagnosticSink>,
+    /// Text currently being scanned, parsed or run, passed to the sink for rendering
+    source: String,
 }
 
 impl SimpleErrorHandler {
@@ -12,28 +17,87 @@ impl SimpleErrorHandler {
         Self {
             had_error: false,
             had_runtime_error: false,
+            source_name: None,
+            sink: Box::new(StderrSink),
+            source: String::new(),
         }
     }
 
-    pub fn error(&mut self, line: u32, message: &str) {
-        self.report(line, "", message);
+    pub fn set_sink(&mut self, sink: Box<dyn DiagnosticSink>) {
+        self.sink = sink;
+    }
+
+    pub fn set_source(&mut self, source: &str) {
+        self.source = String::from(source);
+    }
+
+    pub fn error(&mut self, span: Span, message: &str) {
+        self.report(Stage::Scan, codes::SCAN, span, message);
     }
 
     pub fn parser_error(&mut self, token: &Token, msg: &str) {
-        match token.token_type {
-            TokenType::EOF => self.report(token.line, " at end", msg),
-            _ => self.report(token.line, &format!(" at '{}'", token.lexeme), msg),
-        }
+        self.report(Stage::Parse, codes::PARSE, Span::from(token), msg);
+    }
+
+    pub fn type_error(&mut self, token: &Token, msg: &str) {
+        self.report(Stage::Type, codes::TYPE, Span::from(token), msg);
+    }
+
+    /// Type error covering a whole expression rather than a single token
+    pub fn type_error_at(&mut self, span: Span, msg: &str) {
+        self.report(Stage::Type, codes::TYPE, span, msg);
+    }
+
+    /// Reported by `fmt --check` at the first line the formatter would change
+    pub fn format_error(&mut self, span: Span, msg: &str) {
+        self.report(Stage::Format, codes::FORMAT, span, msg);
+    }
+
+    /// Warnings are shown like errors but never set `had_error`
+    pub fn warning(&mut self, code: &'static str, span: Span, msg: &str) {
+        self.emit(Diagnostic {
+            severity: Severity::Warning,
+            stage: Stage::Lint,
+            code,
+            message: String::from(msg),
+            span,
+            source_name: self.source_name.clone(),
+            notes: Vec::new(),
+        });
     }
 
     pub fn runtime_error(&mut self, error: InterpreterError) {
-        eprintln!("{}", error.message);
-        eprintln!("[line {}]", error.token.line);
+        self.emit(Diagnostic {
+            severity: Severity::Error,
+            stage: Stage::Runtime,
+            code: codes::RUNTIME,
+            message: error.message,
+            span: Span::from(&error.token),
+            source_name: self.source_name.clone(),
+            // A lone frame is just the script, which the line above already shows
+            notes: if error.stack.len() > 1 {
+                error.stack.iter().map(|frame| frame.to_string()).collect()
+            } else {
+                Vec::new()
+            },
+        });
         self.had_runtime_error = true;
     }
 
-    fn report(&mut self, line: u32, location: &str, message: &str) {
-        eprintln!("[line {}] Error{}: {}", line, location, message);
+    fn report(&mut self, stage: Stage, code: &'static str, span: Span, message: &str) {
+        self.emit(Diagnostic {
+            severity: Severity::Error,
+            stage,
+            code,
+            message: String::from(message),
+            span,
+            source_name: self.source_name.clone(),
+            notes: Vec::new(),
+        });
         self.had_error = true;
     }
+
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        self.sink.report(diagnostic, &self.source);
+    }
 }
@@ -0,0 +1,463 @@
+use crate::scanner::token::Token;
+use crate::scanner::token_type::TokenType;
+
+const INDENT: &str = "  ";
+const MAX_WIDTH: usize = 80;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    Paren,
+    /// Parentheses around an `if`, `while` or `for` condition
+    Condition,
+    Square,
+    Block,
+    Enum,
+}
+
+struct Piece {
+    text: String,
+    space_before: bool,
+    /// Number of parentheses and square brackets open before this piece
+    depth: usize,
+    token_type: TokenType,
+}
+
+/// Canonical formatter for Lox source. It works on the token stream from
+/// `Scanner::with_comments` rather than the AST, so comments survive and `for`
+/// loops aren't desugared. Only whitespace between tokens is changed.
+pub struct Formatter<'a> {
+    output: String,
+    /// Pieces of the line being built, written out by `flush`
+    line: Vec<Piece>,
+    line_indent: usize,
+    indent: usize,
+    brackets: Vec<Bracket>,
+    /// The previous token ended a statement or opened a block
+    needs_newline: bool,
+    /// The current statement has been split over several lines by comments
+    continuation: bool,
+    /// Last token written, not counting comments
+    previous: Option<&'a Token>,
+    previous_unary: bool,
+    /// The previous token closed an `if`, `while` or `for` condition
+    after_condition: bool,
+    /// Type and end line of the last token or comment written
+    last_type: Option<TokenType>,
+    last_line: u32,
+    /// An `enum` keyword was seen and its body hasn't opened yet
+    enum_pending: bool,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn format(tokens: &'a [Token]) -> String {
+        let mut formatter = Formatter {
+            output: String::new(),
+            line: Vec::new(),
+            line_indent: 0,
+            indent: 0,
+            brackets: Vec::new(),
+            needs_newline: false,
+            continuation: false,
+            previous: None,
+            previous_unary: false,
+            after_condition: false,
+            last_type: None,
+            last_line: 0,
+            enum_pending: false,
+        };
+        for token in tokens {
+            match token.token_type {
+                TokenType::Eof => break,
+                TokenType::Comment => formatter.comment(token),
+                _ => formatter.token(token),
+            }
+        }
+        formatter.end_line();
+        formatter.output
+    }
+
+    fn comment(&mut self, token: &Token) {
+        let text = token.lexeme.trim_end();
+        if !self.line.is_empty() && token.line == self.last_line {
+            // A trailing comment stays on the line it follows
+            self.push(token, text, true);
+        } else {
+            self.end_line();
+            self.push(token, text, false);
+        }
+        self.end_line();
+    }
+
+    fn token(&mut self, token: &'a Token) {
+        let token_type = token.token_type;
+        let previous_type = self.previous.map(|previous| previous.token_type);
+        match token_type {
+            TokenType::Else if previous_type == Some(TokenType::RightBrace) => {
+                // `} else {` stays on one line
+                self.needs_newline = false;
+            }
+            TokenType::RightBrace => {
+                self.brackets.pop();
+                self.indent = self.indent.saturating_sub(1);
+                if self.needs_newline && previous_type == Some(TokenType::LeftBrace) {
+                    // Empty block
+                    self.needs_newline = false;
+                } else {
+                    self.end_line();
+                    self.continuation = false;
+                }
+            }
+            _ => {
+                if self.needs_newline {
+                    self.end_line();
+                }
+            }
+        }
+
+        let space = self.space_before(token_type);
+        let unary = match token_type {
+            TokenType::Bang => true,
+            TokenType::Minus => {
+                self.after_condition || !previous_type.is_some_and(Formatter::ends_value)
+            }
+            _ => false,
+        };
+        self.push(token, &token.lexeme, space);
+
+        match token_type {
+            TokenType::LeftParen => {
+                let bracket = match previous_type {
+                    Some(TokenType::If) | Some(TokenType::While) | Some(TokenType::For) => {
+                        Bracket::Condition
+                    }
+                    _ => Bracket::Paren,
+                };
+                self.brackets.push(bracket);
+            }
+            TokenType::LeftBracket => self.brackets.push(Bracket::Square),
+            TokenType::LeftBrace => {
+                self.brackets.push(if self.enum_pending {
+                    Bracket::Enum
+                } else {
+                    Bracket::Block
+                });
+                self.enum_pending = false;
+                self.indent += 1;
+                self.needs_newline = true;
+            }
+            TokenType::RightBrace => self.needs_newline = true,
+            TokenType::Comma if self.brackets.last() == Some(&Bracket::Enum) => {
+                self.needs_newline = true
+            }
+            // Semicolons inside `for (...)` don't end a statement
+            TokenType::Semicolon if self.depth() == 0 => self.needs_newline = true,
+            TokenType::Enum => self.enum_pending = true,
+            _ => (),
+        }
+        self.after_condition = false;
+        if matches!(token_type, TokenType::RightParen | TokenType::RightBracket) {
+            self.after_condition = self.brackets.pop() == Some(Bracket::Condition);
+        }
+        self.previous = Some(token);
+        self.previous_unary = unary;
+    }
+
+    fn space_before(&self, token_type: TokenType) -> bool {
+        let previous = match self.previous {
+            Some(previous) if !self.line.is_empty() => previous.token_type,
+            _ => return false,
+        };
+        match token_type {
+            TokenType::RightParen
+            | TokenType::RightBracket
+            | TokenType::Comma
+            | TokenType::Semicolon
+            | TokenType::Dot
+            | TokenType::Colon
+            | TokenType::DotDot
+            | TokenType::DotDotEqual => return false,
+            TokenType::RightBrace if previous == TokenType::LeftBrace => return false,
+            // Calls and indexing
+            TokenType::LeftParen | TokenType::LeftBracket
+                if Formatter::ends_value(previous) && !self.after_condition =>
+            {
+                return false
+            }
+            _ => (),
+        }
+        match previous {
+            TokenType::LeftParen
+            | TokenType::LeftBracket
+            | TokenType::Dot
+            | TokenType::DotDot
+            | TokenType::DotDotEqual => false,
+            TokenType::Bang | TokenType::Minus => !self.previous_unary,
+            _ => true,
+        }
+    }
+
+    /// Whether a token can end an operand, making a following `-` binary
+    fn ends_value(token_type: TokenType) -> bool {
+        matches!(
+            token_type,
+            TokenType::Identifier
+                | TokenType::Number
+                | TokenType::String
+                | TokenType::RightParen
+                | TokenType::RightBracket
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Nil
+                | TokenType::This
+                | TokenType::Super
+        )
+    }
+
+    fn depth(&self) -> usize {
+        self.brackets
+            .iter()
+            .filter(|bracket| !matches!(bracket, Bracket::Block | Bracket::Enum))
+            .count()
+    }
+
+    fn push(&mut self, token: &Token, text: &str, space_before: bool) {
+        if self.line.is_empty() {
+            // Keep at most one blank line between statements, never at the start of a block
+            let blank = token.line > self.last_line + 1
+                && !self.output.is_empty()
+                && !self.continuation
+                && self.last_type != Some(TokenType::LeftBrace)
+                && token.token_type != TokenType::RightBrace;
+            if blank {
+                self.output.push('\n');
+            }
+            // A brace pushed onto its own line by a comment still opens a block at
+            // the statement's level
+            let continuation = self.continuation && token.token_type != TokenType::LeftBrace;
+            self.line_indent = self.indent + continuation as usize;
+        }
+        self.line.push(Piece {
+            text: String::from(text),
+            space_before: space_before && !self.line.is_empty(),
+            depth: self.depth(),
+            token_type: token.token_type,
+        });
+        self.last_type = Some(token.token_type);
+        self.last_line = token.line + token.lexeme.matches('\n').count() as u32;
+    }
+
+    /// Writes out the current line. A line that ends in the middle of a statement makes
+    /// the following lines continuation lines.
+    fn end_line(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        let only_comment = self
+            .line
+            .iter()
+            .all(|piece| piece.token_type == TokenType::Comment);
+        self.flush();
+        if self.needs_newline {
+            self.needs_newline = false;
+            self.continuation = false;
+        } else if !only_comment {
+            self.continuation = true;
+        }
+    }
+
+    fn flush(&mut self) {
+        let indent = INDENT.repeat(self.line_indent);
+        let mut line = indent.clone();
+        for piece in &self.line {
+            if piece.space_before {
+                line.push(' ');
+            }
+            line.push_str(&piece.text);
+        }
+
+        let breaks = self.break_points();
+        if line.chars().count() <= MAX_WIDTH || breaks.is_empty() {
+            self.output.push_str(&line);
+            self.output.push('\n');
+            self.line.clear();
+            return;
+        }
+
+        // Fill each line greedily, wrapping at the break points
+        let continuation = INDENT.repeat(self.line_indent + 1);
+        let mut line = indent;
+        for (i, piece) in self.line.iter().enumerate() {
+            let segment_end = breaks
+                .iter()
+                .find(|point| **point > i)
+                .copied()
+                .unwrap_or(self.line.len());
+            let starts_segment = i > 0 && breaks.contains(&i);
+            if starts_segment {
+                let width: usize = self.line[i..segment_end]
+                    .iter()
+                    .map(|piece| piece.text.chars().count() + piece.space_before as usize)
+                    .sum();
+                if line.chars().count() + width > MAX_WIDTH {
+                    self.output.push_str(&line);
+                    self.output.push('\n');
+                    line = continuation.clone();
+                    line.push_str(&piece.text);
+                    continue;
+                }
+            }
+            if piece.space_before {
+                line.push(' ');
+            }
+            line.push_str(&piece.text);
+        }
+        self.output.push_str(&line);
+        self.output.push('\n');
+        self.line.clear();
+    }
+
+    /// Indices of pieces a long line may wrap before: after the commas at the outermost
+    /// nesting level that has any, or failing that before `and` and `or`
+    fn break_points(&self) -> Vec<usize> {
+        let commas = |depth: usize| {
+            self.line
+                .iter()
+                .enumerate()
+                .filter(move |(_, piece)| {
+                    piece.token_type == TokenType::Comma && piece.depth == depth
+                })
+                .map(|(i, _)| i + 1)
+        };
+        let shallowest = self
+            .line
+            .iter()
+            .filter(|piece| piece.token_type == TokenType::Comma && piece.depth > 0)
+            .map(|piece| piece.depth)
+            .min();
+        let mut points: Vec<usize> = match shallowest {
+            Some(depth) => commas(depth).collect(),
+            None => self
+                .line
+                .iter()
+                .enumerate()
+                .filter(|(_, piece)| matches!(piece.token_type, TokenType::And | TokenType::Or))
+                .map(|(i, _)| i)
+                .collect(),
+        };
+        // Never separate a trailing comment from its line
+        points.retain(|point| {
+            self.line
+                .get(*point)
+                .is_some_and(|piece| piece.token_type != TokenType::Comment)
+        });
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Formatter;
+    use crate::error::SimpleErrorHandler;
+    use crate::scanner::Scanner;
+
+    fn format(source: &str) -> String {
+        let mut error_handler = SimpleErrorHandler::new();
+        let tokens = Scanner::with_comments(source, &mut error_handler).scan_tokens();
+        assert!(!error_handler.had_error);
+        Formatter::format(&tokens)
+    }
+
+    /// Formats `source`, checking that formatting the result changes nothing
+    fn assert_formats(source: &str, expected: &str) {
+        let formatted = format(source);
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            format(&formatted),
+            formatted,
+            "formatting is not idempotent"
+        );
+    }
+
+    #[test]
+    fn lays_out_blocks_and_operators() {
+        assert_formats(
+            "var a=1+2*-3;if(a>1){print a;}else{print-a;}",
+            "var a = 1 + 2 * -3;\nif (a > 1) {\n  print a;\n} else {\n  print -a;\n}\n",
+        );
+    }
+
+    #[test]
+    fn lays_out_enums_calls_and_ranges() {
+        assert_formats(
+            "enum Shape{Circle(r),Square}\nfor(var i in 0 ..= 2)print len( \"ab\" [ i ] );",
+            "enum Shape {\n  Circle(r),\n  Square\n}\nfor (var i in 0..=2) print len(\"ab\"[i]);\n",
+        );
+    }
+
+    #[test]
+    fn keeps_one_blank_line_between_statements() {
+        assert_formats(
+            "print 1;\n\n\n\nprint 2;\n{\n\n  print 3;\n}\n",
+            "print 1;\n\nprint 2;\n{\n  print 3;\n}\n",
+        );
+    }
+
+    #[test]
+    fn keeps_empty_blocks_on_one_line() {
+        assert_formats("while (false) {\n}\n", "while (false) {}\n");
+    }
+
+    #[test]
+    fn keeps_comments_on_their_own_lines_and_trailing() {
+        assert_formats(
+            "// header\nprint 1; // one\n  // two\nprint 2;\n",
+            "// header\nprint 1; // one\n// two\nprint 2;\n",
+        );
+    }
+
+    #[test]
+    fn keeps_a_comment_after_else() {
+        assert_formats(
+            "if (true) { print 1; } else // otherwise\n{ print 2; }\n",
+            "if (true) {\n  print 1;\n} else // otherwise\n{\n  print 2;\n}\n",
+        );
+        assert_formats(
+            "if (true) print 1; else // otherwise\nprint 2;\n",
+            "if (true) print 1;\nelse // otherwise\n  print 2;\n",
+        );
+    }
+
+    #[test]
+    fn keeps_comments_inside_a_for_header() {
+        assert_formats(
+            "for (var i = 0; // start\ni < 3; // stop\ni = i + 1) print i;\n",
+            "for (var i = 0; // start\n  i < 3; // stop\n  i = i + 1) print i;\n",
+        );
+    }
+
+    #[test]
+    fn keeps_comments_in_the_middle_of_an_expression() {
+        assert_formats(
+            "var x = 1 + // first\n2 + // second\n3;\nprint x;\n",
+            "var x = 1 + // first\n  2 + // second\n  3;\nprint x;\n",
+        );
+    }
+
+    #[test]
+    fn wraps_long_lines_after_commas() {
+        let source = "print max(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccc, dddddddddd);";
+        assert_formats(
+            source,
+            "print max(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccc,\n  dddddddddd);\n",
+        );
+    }
+
+    #[test]
+    fn wraps_long_conditions_before_and_or() {
+        let source = "if (aaaaaaaaaaaaaaaaaaaa == 1 and bbbbbbbbbbbbbbbbbbbb == 2 or cccccccccccccccccccc == 3) print 1;";
+        assert_formats(
+            source,
+            "if (aaaaaaaaaaaaaaaaaaaa == 1 and bbbbbbbbbbbbbbbbbbbb == 2\n  or cccccccccccccccccccc == 3) print 1;\n",
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+
+use super::value::Value;
+use super::Interpreter;
+use crate::error::SimpleErrorHandler;
+use crate::parser::{stmt, Parser};
+use crate::scanner::Scanner;
+
+const HELP: &str = "\
+break N, b N     stop before statements on line N
+delete N, d N    remove the breakpoint on line N
+continue, c      run to the next breakpoint
+step, s          stop at the next statement, including inside blocks and loops
+next, n          stop at the next statement that isn't nested in this one
+out, o           stop at the next statement after the enclosing loop or if
+print EXPR, p    evaluate an expression in the current scope
+scopes           list the variables in each scope, innermost first
+list, l          show the source around the current line
+quit, q          stop the program";
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Continue,
+    StepInto,
+    /// Stop at a statement nested no deeper than the given depth
+    StepOver(usize),
+    /// Stop at a statement nested less deeply than the given depth
+    StepOut(usize),
+}
+
+/// Interactive debugger for `rlox debug`. The interpreter calls `before_statement`
+/// before running each statement, and the debugger reads commands from its input
+/// whenever it stops. Execution starts paused at the first statement.
+pub struct Debugger {
+    lines: Vec<String>,
+    breakpoints: BTreeSet<u32>,
+    mode: Mode,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Debugger {
+    /// Debugger that talks to the user over stdin and stdout
+    pub fn new(source: &str) -> Self {
+        Self::with_io(
+            source,
+            Box::new(BufReader::new(io::stdin())),
+            Box::new(io::stdout()),
+        )
+    }
+
+    pub fn with_io(source: &str, input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Self {
+            lines: source.lines().map(String::from).collect(),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::StepInto,
+            input,
+            output,
+        }
+    }
+
+    pub(super) fn before_statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        statement: &stmt::Stmt,
+    ) {
+        // Blocks are stepped through rather than stopped at
+        let line = match statement.line() {
+            Some(line) => line,
+            None => return,
+        };
+        let depth = interpreter.depth;
+        let stop = self.breakpoints.contains(&line)
+            || match self.mode {
+                Mode::Continue => false,
+                Mode::StepInto => true,
+                Mode::StepOver(over) => depth <= over,
+                Mode::StepOut(out) => depth < out,
+            };
+        if stop {
+            self.show_line(line, "->");
+            self.prompt(interpreter, line, depth);
+        }
+    }
+
+    fn prompt(&mut self, interpreter: &mut Interpreter, line: u32, depth: usize) {
+        let mut input = String::new();
+        loop {
+            write!(self.output, "(debug) ").unwrap();
+            self.output.flush().unwrap();
+            input.clear();
+            if self.input.read_line(&mut input).unwrap_or(0) == 0 {
+                // Nobody left to ask, so let the program finish
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                writeln!(self.output).unwrap();
+                return;
+            }
+            let input = input.trim();
+            let (command, argument) = match input.split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (input, ""),
+            };
+            match command {
+                "" => (),
+                "c" | "continue" => {
+                    self.mode = Mode::Continue;
+                    return;
+                }
+                "s" | "step" => {
+                    self.mode = Mode::StepInto;
+                    return;
+                }
+                "n" | "next" => {
+                    self.mode = Mode::StepOver(depth);
+                    return;
+                }
+                "o" | "out" => {
+                    self.mode = Mode::StepOut(depth);
+                    return;
+                }
+                "b" | "break" => match self.line_number(argument) {
+                    Some(number) => {
+                        self.breakpoints.insert(number);
+                        writeln!(self.output, "Breakpoint set on line {}.", number).unwrap();
+                    }
+                    None => writeln!(self.output, "Expect a line number.").unwrap(),
+                },
+                "d" | "delete" => match self.line_number(argument) {
+                    Some(number) if self.breakpoints.remove(&number) => {
+                        writeln!(self.output, "Breakpoint on line {} removed.", number).unwrap()
+                    }
+                    Some(number) => {
+                        writeln!(self.output, "No breakpoint on line {}.", number).unwrap()
+                    }
+                    None => writeln!(self.output, "Expect a line number.").unwrap(),
+                },
+                "p" | "print" => self.evaluate(interpreter, argument),
+                "scopes" => self.scopes(interpreter),
+                "l" | "list" => {
+                    let first = line.saturating_sub(2).max(1);
+                    for number in first..=line + 2 {
+                        let marker = if number == line { "->" } else { "  " };
+                        self.show_line(number, marker);
+                    }
+                }
+                "h" | "help" => writeln!(self.output, "{}", HELP).unwrap(),
+                "q" | "quit" => process::exit(0),
+                _ => writeln!(
+                    self.output,
+                    "Unknown command '{}'. Type 'help' for a list.",
+                    command
+                )
+                .unwrap(),
+            }
+        }
+    }
+
+    fn line_number(&self, argument: &str) -> Option<u32> {
+        argument
+            .parse()
+            .ok()
+            .filter(|number| *number >= 1 && *number as usize <= self.lines.len())
+    }
+
+    /// Prints a source line, marking breakpoints with `*`
+    fn show_line(&mut self, number: u32, marker: &str) {
+        if let Some(text) = self.lines.get(number as usize - 1) {
+            let breakpoint = if self.breakpoints.contains(&number) {
+                "*"
+            } else {
+                " "
+            };
+            writeln!(
+                self.output,
+                "{}{}{:>4} | {}",
+                marker, breakpoint, number, text
+            )
+            .unwrap();
+        }
+    }
+
+    fn evaluate(&mut self, interpreter: &mut Interpreter, input: &str) {
+        // Errors in the expression are reported against the expression, not the script
+        let source = format!("{};", input);
+        let mut error_handler = SimpleErrorHandler::new();
+        error_handler.set_source(&source);
+        let tokens = Scanner::new(&source, &mut error_handler).scan_tokens();
+        let statements = Parser::new(tokens, &mut error_handler).parse();
+        if error_handler.had_error {
+            return;
+        }
+        match statements.as_slice() {
+            [stmt::Stmt::Expression(statement)] => {
+                match interpreter.evaluate(&statement.expression) {
+                    Ok(value) => writeln!(self.output, "{}", Interpreter::quoted(&value)),
+                    Err(error) => writeln!(self.output, "Error: {}", error.message),
+                }
+                .unwrap();
+            }
+            _ => writeln!(self.output, "Expect an expression.").unwrap(),
+        }
+    }
+
+    fn scopes(&mut self, interpreter: &Interpreter) {
+        let mut environment = Some(interpreter.environment.clone());
+        let mut depth = 0;
+        while let Some(scope) = environment {
+            let scope = scope.borrow();
+            let enclosing = scope.enclosing();
+            let values = scope.values();
+            if enclosing.is_some() {
+                writeln!(self.output, "scope {}:", depth).unwrap();
+            } else {
+                writeln!(self.output, "globals:").unwrap();
+            }
+            let mut natives = 0;
+            for (name, value) in &values {
+                // The standard library would drown out the program's own globals
+                if enclosing.is_none() && matches!(value, Value::NativeFunction(_)) {
+                    natives += 1;
+                    continue;
+                }
+                writeln!(self.output, "  {} = {}", name, Interpreter::quoted(value)).unwrap();
+            }
+            if natives > 0 {
+                writeln!(self.output, "  ({} native functions not shown)", natives).unwrap();
+            }
+            environment = enclosing;
+            depth += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Cursor, Write};
+    use std::rc::Rc;
+
+    use super::Debugger;
+    use crate::error::SimpleErrorHandler;
+    use crate::interpreter::environment::Environment;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[derive(Clone)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `source` under the debugger, typing `commands` one per line, and returns
+    /// everything the debugger printed
+    fn debug(source: &str, commands: &[&str]) -> String {
+        let mut error_handler = SimpleErrorHandler::new();
+        error_handler.set_source(source);
+        let tokens = Scanner::new(source, &mut error_handler).scan_tokens();
+        let statements = Parser::new(tokens, &mut error_handler).parse();
+        assert!(!error_handler.had_error, "{:?} doesn't parse", source);
+
+        let input: String = commands
+            .iter()
+            .map(|command| format!("{}\n", command))
+            .collect();
+        let output = Output(Rc::new(RefCell::new(Vec::new())));
+        let mut interpreter = Interpreter::new(
+            &mut error_handler,
+            Rc::new(RefCell::new(Environment::new())),
+        );
+        interpreter.set_debugger(Debugger::with_io(
+            source,
+            Box::new(Cursor::new(input)),
+            Box::new(output.clone()),
+        ));
+        interpreter.interpret(&statements);
+        assert!(!error_handler.had_runtime_error);
+        let bytes = output.0.take();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    /// Lines the debugger stopped on, in order
+    fn stops(output: &str) -> Vec<u32> {
+        output
+            .lines()
+            .map(|line| line.trim_start_matches("(debug) "))
+            .filter_map(|line| line.strip_prefix("->"))
+            .map(|line| {
+                line[1..]
+                    .split(" |")
+                    .next()
+                    .unwrap()
+                    .trim()
+                    .parse()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn step_stops_at_every_statement() {
+        let source = "var a = 1;\nif (a > 0) {\n  a = 2;\n}\nvar b = a;";
+        let output = debug(source, &["s", "s", "s", "s"]);
+        assert_eq!(stops(&output), vec![1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn next_steps_over_a_for_loop() {
+        let source = "var a = 1;\nfor (var i = 0; i < 2; i = i + 1) a = a + i;\nvar done = true;";
+        let output = debug(source, &["n", "n", "n"]);
+        assert_eq!(stops(&output), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn next_steps_over_a_loop_without_clauses() {
+        let source = "var a = 0;\nfor (; a < 2;) a = a + 1;\nvar b = a;";
+        let output = debug(source, &["n", "n", "p a"]);
+        assert_eq!(stops(&output), vec![1, 2, 3]);
+        assert!(output.contains("(debug) 2\n"));
+    }
+
+    #[test]
+    fn next_enters_braces() {
+        let source = "var a = 1;\n{\n  var b = 2;\n}\nvar c = 3;";
+        let output = debug(source, &["n", "n"]);
+        assert_eq!(stops(&output), vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn out_leaves_the_enclosing_loop() {
+        let source = "var i = 0;\nwhile (i < 3) {\n  i = i + 1;\n}\nvar j = i;";
+        let output = debug(source, &["s", "s", "o", "p i"]);
+        assert_eq!(stops(&output), vec![1, 2, 3, 5]);
+        assert!(output.ends_with("(debug) 3\n(debug) \n"), "{}", output);
+    }
+
+    #[test]
+    fn continue_stops_at_breakpoints() {
+        let source = "var i = 0;\nwhile (i < 3) {\n  i = i + 1;\n}\nvar j = i;";
+        let output = debug(source, &["b 3", "c", "p i", "c", "p i", "d 3", "c"]);
+        assert_eq!(stops(&output), vec![1, 3, 3]);
+        assert!(output.contains("Breakpoint set on line 3."));
+        assert!(output.contains("(debug) 0\n"));
+        assert!(output.contains("(debug) 1\n"));
+        assert!(output.contains("Breakpoint on line 3 removed."));
+    }
+
+    #[test]
+    fn lists_scopes_innermost_first() {
+        let source = "var a = 1;\n{\n  var b = \"two\";\n  var c = b;\n}";
+        let output = debug(source, &["n", "n", "scopes", "c"]);
+        assert!(
+            output.contains("scope 0:\n  b = \"two\"\nglobals:\n  a = 1\n"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn lets_the_program_finish_when_input_ends() {
+        let output = debug("var a = 1;\nvar b = 2;", &[]);
+        assert_eq!(stops(&output), vec![1]);
+    }
+
+    #[test]
+    fn reports_bad_commands() {
+        let output = debug("var a = 1;", &["b 99", "d 1", "jump", "c"]);
+        assert!(output.contains("Expect a line number."));
+        assert!(output.contains("No breakpoint on line 1."));
+        assert!(output.contains("Unknown command 'jump'. Type 'help' for a list."));
+    }
+}
@@ -3,11 +3,25 @@ use crate::scanner::token::Token;
 use super::value::Value;
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
+/// A name defined in an environment, described for tools such as the language server
+pub struct Binding {
+    pub name: String,
+    /// Short signature, e.g. `fn sqrt/1` or `var TAU`
+    pub detail: String,
+    pub callable: bool,
+}
+
 pub struct Environment {
     values: HashMap<String, Value>,
     enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Environment {
     pub fn new() -> Self {
         Self {
@@ -40,6 +54,54 @@ impl Environment {
         }
     }
 
+    /// Every name visible from this scope, including shadowed ones
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
+        if let Some(enclosing) = &self.enclosing {
+            names.extend(enclosing.borrow().names());
+        }
+        names
+    }
+
+    /// Names and values defined directly in this scope, sorted by name
+    pub fn values(&self) -> Vec<(String, Value)> {
+        let mut values: Vec<(String, Value)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values
+    }
+
+    pub fn enclosing(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.enclosing.clone()
+    }
+
+    /// Describes the names defined directly in this scope, sorted by name
+    pub fn bindings(&self) -> Vec<Binding> {
+        let mut bindings: Vec<Binding> = self
+            .values
+            .iter()
+            .map(|(name, value)| {
+                let (detail, callable) = match value {
+                    Value::NativeFunction(function) => {
+                        (format!("fn {}/{}", name, function.arity), true)
+                    }
+                    Value::Enum(_) => (format!("enum {}", name), false),
+                    _ => (format!("var {}", name), false),
+                };
+                Binding {
+                    name: name.clone(),
+                    detail,
+                    callable,
+                }
+            })
+            .collect();
+        bindings.sort_by(|a, b| a.name.cmp(&b.name));
+        bindings
+    }
+
     pub fn get(&self, name: &Token) -> Option<Value> {
         match self.values.get(&name.lexeme) {
             Some(val) => Some(val.clone()),
@@ -1,4 +1,6 @@
+use crate::diagnostic::Span;
 use crate::scanner::token::Token;
+use crate::scanner::token_type::TokenType;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +8,10 @@ use thiserror::Error;
 pub struct InterpreterError {
     pub token: Token,
     pub message: String,
+    /// Active calls when the error was raised, innermost first. Empty from `new`; the
+    /// interpreter fills it in when the error comes out of a native, or otherwise when
+    /// it reaches the top of the script.
+    pub stack: Vec<Frame>,
 }
 
 impl InterpreterError {
@@ -13,6 +19,41 @@ impl InterpreterError {
         Self {
             token,
             message: String::from(message),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Error for a stretch of source rather than a single token, e.g. a part of the
+    /// tree that failed to parse
+    pub fn at_span(span: Span, message: &str) -> Self {
+        let token = Token::new(
+            TokenType::Identifier,
+            String::new(),
+            None,
+            span.line,
+            span.column,
+            span.start,
+            span.end,
+        );
+        Self::new(token, message)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub function: String,
+    /// Source the frame is executing, `None` for the main script and for natives
+    pub file: Option<String>,
+    /// Line currently executing in the frame, `None` for natives
+    pub line: Option<u32>,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, self.line) {
+            (_, None) => write!(f, "at {} (native)", self.function),
+            (Some(file), Some(line)) => write!(f, "at {} ({} line {})", self.function, file, line),
+            (None, Some(line)) => write!(f, "at {} (line {})", self.function, line),
         }
     }
 }
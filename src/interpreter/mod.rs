@@ -3,18 +3,29 @@ use std::rc::Rc;
 
 use crate::error::SimpleErrorHandler;
 use crate::parser::{expr, stmt};
-use crate::scanner::token::Literal;
+use crate::scanner::token::{Literal, Token};
 use crate::scanner::token_type::TokenType;
+pub mod debugger;
 pub mod environment;
 pub mod error;
+pub mod native;
+mod suggest;
 mod value;
+use debugger::Debugger;
 use environment::Environment;
-use error::InterpreterError;
-use value::Value;
+use error::{Frame, InterpreterError};
+use value::{EnumType, Range, Value, VariantDef, VariantValue};
 
 pub struct Interpreter<'a> {
     error_handler: &'a mut SimpleErrorHandler,
     environment: Rc<RefCell<Environment>>,
+    /// Active calls, outermost (the script itself) first
+    call_stack: Vec<Frame>,
+    /// Consulted before every statement when running under `rlox debug`
+    debugger: Option<Debugger>,
+    /// Number of statements the debugger can stop at that are currently executing, for
+    /// stepping over and out. Blocks from braces have no line and don't count.
+    depth: usize,
 }
 
 impl<'a> Interpreter<'a> {
@@ -22,44 +33,59 @@ impl<'a> Interpreter<'a> {
         error_handler: &'a mut SimpleErrorHandler,
         environment: Rc<RefCell<Environment>>,
     ) -> Self {
+        let script = Frame {
+            function: String::from("<script>"),
+            file: error_handler.source_name.clone(),
+            line: None,
+        };
         Self {
             error_handler,
             environment,
+            call_stack: vec![script],
+            debugger: None,
+            depth: 0,
         }
     }
 
+    pub fn set_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger);
+    }
+
     fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Result<Value> {
         let value = self.evaluate(&expr.value)?;
-        match self
+        let previous = self
             .environment
             .borrow_mut()
-            .assign(&expr.name, value.clone())
-        {
+            .assign(&expr.name, value.clone());
+        match previous {
             Some(_) => Ok(value), // Discard old value - we want to return updated value
-            None => Err(InterpreterError::new(
-                expr.name.clone(),
-                &format!("Undefined variable '{}'.", expr.name.lexeme),
-            )),
+            None => Err(self.undefined_variable(&expr.name)),
         }
     }
 
     fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Result<Value> {
         let left = self.evaluate(&expr.left)?;
         let right = self.evaluate(&expr.right)?;
+        Interpreter::binary_operation(&expr.operator, &left, &right)
+    }
 
-        let equality = match expr.operator.token_type {
+    fn binary_operation(operator: &Token, left: &Value, right: &Value) -> Result<Value> {
+        if operator.token_type == TokenType::Is {
+            return Interpreter::is_variant(operator, left, right);
+        }
+
+        let equality = match operator.token_type {
             TokenType::BangEqual => Some(Value::Boolean(!(left == right))),
             TokenType::EqualEqual => Some(Value::Boolean(left == right)),
             _ => None,
         };
 
-        match equality {
-            Some(val) => return Ok(val),
-            None => (),
+        if let Some(val) = equality {
+            return Ok(val);
         };
 
-        if let (Value::Number(left_num), Value::Number(right_num)) = (&left, &right) {
-            match expr.operator.token_type {
+        if let (Value::Number(left_num), Value::Number(right_num)) = (left, right) {
+            match operator.token_type {
                 TokenType::Greater => Ok(Value::Boolean(left_num > right_num)),
                 TokenType::GreaterEqual => Ok(Value::Boolean(left_num >= right_num)),
                 TokenType::Less => Ok(Value::Boolean(left_num < right_num)),
@@ -69,30 +95,212 @@ impl<'a> Interpreter<'a> {
                 TokenType::Slash => Ok(Value::Number(left_num / right_num)),
                 TokenType::Star => Ok(Value::Number(left_num * right_num)),
                 _ => Err(InterpreterError::new(
-                    expr.operator.clone(),
+                    operator.clone(),
                     "Invalid infix operator for two numbers",
                 )),
             }
-        } else if let (Value::String(left_str), Value::String(right_str)) = (&left, &right) {
-            match expr.operator.token_type {
-                TokenType::Plus => Ok(Value::String(format!("{}{}", &left_str, &right_str))),
+        } else if let (Value::String(left_str), Value::String(right_str)) = (left, right) {
+            match operator.token_type {
+                TokenType::Plus => Ok(Value::String(format!("{}{}", left_str, right_str))),
                 _ => Err(InterpreterError::new(
-                    expr.operator.clone(),
+                    operator.clone(),
                     "Invalid infix operator for two strings",
                 )),
             }
         } else {
             Err(InterpreterError::new(
-                expr.operator.clone(),
+                operator.clone(),
                 "Invalid binary operation",
             ))
         }
     }
 
+    /// `value is Enum.Variant` - the right side is a constructor or a unit variant
+    fn is_variant(operator: &Token, value: &Value, variant: &Value) -> Result<Value> {
+        let (enum_type, index) = match variant {
+            Value::VariantConstructor(enum_type, index) => (enum_type, *index),
+            Value::Variant(unit) if unit.fields.is_empty() => (&unit.enum_type, unit.variant),
+            _ => {
+                return Err(InterpreterError::new(
+                    operator.clone(),
+                    "Right operand of 'is' must be an enum variant",
+                ))
+            }
+        };
+        Ok(Value::Boolean(match value {
+            Value::Variant(value) => value.enum_type == *enum_type && value.variant == index,
+            _ => false,
+        }))
+    }
+
+    fn visit_call_expr(&mut self, expr: &expr::Call) -> Result<Value> {
+        let callee = self.evaluate(&expr.callee)?;
+
+        let mut arguments = Vec::new();
+        for argument in &expr.arguments {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        match callee {
+            Value::NativeFunction(function) => {
+                if arguments.len() != function.arity {
+                    return Err(InterpreterError::new(
+                        expr.paren.clone(),
+                        &format!(
+                            "Expected {} arguments but got {}.",
+                            function.arity,
+                            arguments.len()
+                        ),
+                    ));
+                }
+                self.call_stack.last_mut().unwrap().line = Some(expr.paren.line);
+                self.call_stack.push(Frame {
+                    function: String::from(function.name),
+                    file: None,
+                    line: None,
+                });
+                let result = function.call(&arguments).map_err(|message| {
+                    let mut error = InterpreterError::new(expr.paren.clone(), &message);
+                    error.stack = self.stack_trace();
+                    error
+                });
+                self.call_stack.pop();
+                result
+            }
+            Value::VariantConstructor(enum_type, variant) => {
+                let arity = match &enum_type.variants[variant].fields {
+                    Some(fields) => fields.len(),
+                    None => 0,
+                };
+                if arguments.len() != arity {
+                    return Err(InterpreterError::new(
+                        expr.paren.clone(),
+                        &format!("Expected {} arguments but got {}.", arity, arguments.len()),
+                    ));
+                }
+                Ok(Value::Variant(Rc::new(VariantValue {
+                    enum_type,
+                    variant,
+                    fields: arguments,
+                })))
+            }
+            _ => Err(InterpreterError::new(
+                expr.paren.clone(),
+                "Can only call functions.",
+            )),
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) -> Result<Value> {
+        match self.evaluate(&expr.object)? {
+            Value::Enum(enum_type) => match enum_type.variant(&expr.name.lexeme) {
+                Some(variant) => Ok(match enum_type.variants[variant].fields {
+                    Some(_) => Value::VariantConstructor(enum_type, variant),
+                    None => Value::Variant(Rc::new(VariantValue {
+                        enum_type: enum_type.clone(),
+                        variant,
+                        fields: Vec::new(),
+                    })),
+                }),
+                None => Err(InterpreterError::new(
+                    expr.name.clone(),
+                    &format!(
+                        "Enum '{}' has no variant '{}'.{}",
+                        enum_type.name,
+                        expr.name.lexeme,
+                        suggest::did_you_mean(
+                            &expr.name.lexeme,
+                            enum_type
+                                .variants
+                                .iter()
+                                .map(|variant| variant.name.as_str())
+                        )
+                    ),
+                )),
+            },
+            Value::Variant(value) => {
+                let variant = &value.enum_type.variants[value.variant];
+                let field = variant
+                    .fields
+                    .as_ref()
+                    .and_then(|fields| fields.iter().position(|field| field == &expr.name.lexeme));
+                match field {
+                    Some(field) => Ok(value.fields[field].clone()),
+                    None => Err(InterpreterError::new(
+                        expr.name.clone(),
+                        &format!(
+                            "Undefined field '{}' on {}.{}.{}",
+                            expr.name.lexeme,
+                            value.enum_type.name,
+                            variant.name,
+                            suggest::did_you_mean(
+                                &expr.name.lexeme,
+                                variant.fields.iter().flatten().map(String::as_str)
+                            )
+                        ),
+                    )),
+                }
+            }
+            _ => Err(InterpreterError::new(
+                expr.name.clone(),
+                "Only enums and enum values have properties",
+            )),
+        }
+    }
+
     fn visit_grouping_expr(&mut self, expr: &expr::Grouping) -> Result<Value> {
         self.evaluate(&expr.expression)
     }
 
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Result<Value> {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+
+        match (&object, &index) {
+            (Value::String(string), Value::Range(range)) => {
+                let chars: Vec<char> = string.chars().collect();
+                let (start, end) = range.bounds(chars.len()).ok_or_else(|| {
+                    InterpreterError::new(
+                        expr.bracket.clone(),
+                        &format!(
+                            "Slice {} out of bounds for string of length {}.",
+                            index,
+                            chars.len()
+                        ),
+                    )
+                })?;
+                Ok(Value::String(chars[start..end].iter().collect()))
+            }
+            (Value::String(string), Value::Number(position)) => {
+                let range = Range {
+                    start: *position,
+                    end: *position,
+                    inclusive: true,
+                };
+                let chars: Vec<char> = string.chars().collect();
+                let (start, _) = range.bounds(chars.len()).ok_or_else(|| {
+                    InterpreterError::new(
+                        expr.bracket.clone(),
+                        &format!(
+                            "Index {} out of bounds for string of length {}.",
+                            index,
+                            chars.len()
+                        ),
+                    )
+                })?;
+                Ok(Value::String(chars[start].to_string()))
+            }
+            (Value::String(_), _) => Err(InterpreterError::new(
+                expr.bracket.clone(),
+                "String index must be a number or a range",
+            )),
+            _ => Err(InterpreterError::new(
+                expr.bracket.clone(),
+                "Only strings can be indexed",
+            )),
+        }
+    }
+
     fn visit_literal_expr(&self, expr: &expr::Literal) -> Result<Value> {
         Ok(match &*expr.value {
             Literal::Number(num) => Value::Number(*num),
@@ -118,6 +326,24 @@ impl<'a> Interpreter<'a> {
         self.evaluate(&expr.right)
     }
 
+    fn visit_range_expr(&mut self, expr: &expr::Range) -> Result<Value> {
+        let start = self.evaluate(&expr.start)?;
+        let end = self.evaluate(&expr.end)?;
+
+        if let (Value::Number(start), Value::Number(end)) = (start, end) {
+            Ok(Value::Range(Range {
+                start,
+                end,
+                inclusive: expr.operator.token_type == TokenType::DotDotEqual,
+            }))
+        } else {
+            Err(InterpreterError::new(
+                expr.operator.clone(),
+                "Range bounds must be numbers",
+            ))
+        }
+    }
+
     fn visit_unary_expr(&mut self, expr: &expr::Unary) -> Result<Value> {
         let right = self.evaluate(&expr.right)?;
 
@@ -141,15 +367,25 @@ impl<'a> Interpreter<'a> {
     }
 
     fn visit_variable_expr(&self, expr: &expr::Variable) -> Result<Value> {
-        match self.environment.borrow().get(&expr.name) {
-            Some(val) => Ok(val.clone()),
-            None => Err(InterpreterError::new(
-                expr.name.clone(),
-                &format!("Undefined variable '{}'.", expr.name.lexeme),
-            )),
+        let value = self.environment.borrow().get(&expr.name);
+        match value {
+            Some(val) => Ok(val),
+            None => Err(self.undefined_variable(&expr.name)),
         }
     }
 
+    fn undefined_variable(&self, name: &Token) -> InterpreterError {
+        let names = self.environment.borrow().names();
+        InterpreterError::new(
+            name.clone(),
+            &format!(
+                "Undefined variable '{}'.{}",
+                name.lexeme,
+                suggest::did_you_mean(&name.lexeme, names.iter().map(String::as_str))
+            ),
+        )
+    }
+
     fn is_truthy(val: &Value) -> bool {
         match val {
             Value::Nil => false,
@@ -162,14 +398,76 @@ impl<'a> Interpreter<'a> {
         match expr {
             expr::Expr::Assign(assign) => self.visit_assign_expr(assign),
             expr::Expr::Binary(binary) => self.visit_binary_expr(binary),
+            expr::Expr::Call(call) => self.visit_call_expr(call),
+            expr::Expr::Error(error) => Err(InterpreterError::at_span(
+                error.span,
+                "Cannot run an expression that failed to parse.",
+            )),
+            expr::Expr::Get(get) => self.visit_get_expr(get),
             expr::Expr::Grouping(grouping) => self.visit_grouping_expr(grouping),
+            expr::Expr::Index(index) => self.visit_index_expr(index),
             expr::Expr::Literal(literal) => self.visit_literal_expr(literal),
             expr::Expr::Logical(logical) => self.visit_logical_expr(logical),
+            expr::Expr::Range(range) => self.visit_range_expr(range),
             expr::Expr::Unary(unary) => self.visit_unary_expr(unary),
             expr::Expr::Variable(variable) => self.visit_variable_expr(variable),
         }
     }
 
+    fn visit_assert_stmt(&mut self, stmt: &stmt::Assert) -> Result<()> {
+        let mut detail = None;
+        let passed = match &*stmt.condition {
+            // Evaluate the operands separately so the failure can show both values
+            expr::Expr::Binary(binary)
+                if Interpreter::negated_comparison(&binary.operator).is_some() =>
+            {
+                let left = self.evaluate(&binary.left)?;
+                let right = self.evaluate(&binary.right)?;
+                let result = Interpreter::binary_operation(&binary.operator, &left, &right)?;
+                detail = Some(format!(
+                    "{} {} {}",
+                    Interpreter::quoted(&left),
+                    Interpreter::negated_comparison(&binary.operator).unwrap(),
+                    Interpreter::quoted(&right)
+                ));
+                Interpreter::is_truthy(&result)
+            }
+            condition => Interpreter::is_truthy(&self.evaluate(condition)?),
+        };
+        if passed {
+            return Ok(());
+        }
+
+        let mut message = format!("assert {} failed", stmt.source);
+        if let Some(detail) = detail {
+            message.push_str(&format!(": {}", detail));
+        }
+        if let Some(user_message) = &stmt.message {
+            message.push_str(&format!(" ({})", self.evaluate(user_message)?));
+        }
+        Err(InterpreterError::new(stmt.keyword.clone(), &message))
+    }
+
+    /// The operator that holds between the operands when a comparison is false
+    fn negated_comparison(operator: &Token) -> Option<&'static str> {
+        match operator.token_type {
+            TokenType::EqualEqual => Some("!="),
+            TokenType::BangEqual => Some("=="),
+            TokenType::Greater => Some("<="),
+            TokenType::GreaterEqual => Some("<"),
+            TokenType::Less => Some(">="),
+            TokenType::LessEqual => Some(">"),
+            _ => None,
+        }
+    }
+
+    fn quoted(value: &Value) -> String {
+        match value {
+            Value::String(string) => format!("\"{}\"", string),
+            _ => value.to_string(),
+        }
+    }
+
     fn visit_block_stmt(&mut self, stmt: &stmt::Block) -> Result<()> {
         self.execute_block(
             &stmt.statements,
@@ -196,11 +494,54 @@ impl<'a> Interpreter<'a> {
         Ok(())
     }
 
+    fn visit_enum_stmt(&mut self, stmt: &stmt::Enum) -> Result<()> {
+        let enum_type = EnumType {
+            name: stmt.name.lexeme.clone(),
+            variants: stmt
+                .variants
+                .iter()
+                .map(|variant| VariantDef {
+                    name: variant.name.lexeme.clone(),
+                    fields: variant
+                        .fields
+                        .as_ref()
+                        .map(|fields| fields.iter().map(|field| field.lexeme.clone()).collect()),
+                })
+                .collect(),
+        };
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name.lexeme, Value::Enum(Rc::new(enum_type)));
+        Ok(())
+    }
+
     fn visit_expression_stmt(&mut self, stmt: &stmt::Expression) -> Result<()> {
         self.evaluate(&stmt.expression)?;
         Ok(())
     }
 
+    fn visit_for_in_stmt(&mut self, stmt: &stmt::ForIn) -> Result<()> {
+        let range = match self.evaluate(&stmt.iterable)? {
+            Value::Range(range) => range,
+            _ => {
+                return Err(InterpreterError::new(
+                    stmt.name.clone(),
+                    "Can only iterate over a range",
+                ))
+            }
+        };
+
+        for i in range.iter() {
+            let mut environment = Environment::with_enclosing(self.environment.clone());
+            environment.define(&stmt.name.lexeme, Value::Number(i));
+            self.execute_block(
+                std::slice::from_ref(&*stmt.body),
+                Rc::new(RefCell::new(environment)),
+            )?;
+        }
+        Ok(())
+    }
+
     fn visit_if_stmt(&mut self, stmt: &stmt::If) -> Result<()> {
         if Interpreter::is_truthy(&self.evaluate(&stmt.condition)?) {
             self.execute(&stmt.then_branch)?;
@@ -230,11 +571,21 @@ impl<'a> Interpreter<'a> {
         Ok(())
     }
 
+    /// Snapshot of the active calls, innermost first
+    fn stack_trace(&self) -> Vec<Frame> {
+        self.call_stack.iter().rev().cloned().collect()
+    }
+
     pub fn interpret(&mut self, statements: &[stmt::Stmt]) {
         for statement in statements {
             match self.execute(statement) {
                 Ok(_) => (),
-                Err(err) => {
+                Err(mut err) => {
+                    if err.stack.is_empty() {
+                        // Raised by the script itself rather than inside a call
+                        self.call_stack.last_mut().unwrap().line = Some(err.token.line);
+                        err.stack = self.stack_trace();
+                    }
                     self.error_handler.runtime_error(err);
                     return;
                 }
@@ -243,17 +594,85 @@ impl<'a> Interpreter<'a> {
     }
 
     fn execute(&mut self, statement: &stmt::Stmt) -> Result<()> {
-        match statement {
+        // Taken out while paused so the debugger can use the interpreter
+        if let Some(mut debugger) = self.debugger.take() {
+            debugger.before_statement(self, statement);
+            self.debugger = Some(debugger);
+        }
+
+        let nests = statement.line().is_some();
+        if nests {
+            self.depth += 1;
+        }
+        let result = match statement {
+            stmt::Stmt::Assert(assert_statement) => self.visit_assert_stmt(assert_statement),
             stmt::Stmt::Block(block_statement) => self.visit_block_stmt(block_statement),
+            stmt::Stmt::Enum(enum_statement) => self.visit_enum_stmt(enum_statement),
+            stmt::Stmt::Error(error) => Err(InterpreterError::at_span(
+                error.span,
+                "Cannot run a statement that failed to parse.",
+            )),
             stmt::Stmt::Expression(expression_statement) => {
                 self.visit_expression_stmt(expression_statement)
             }
+            stmt::Stmt::ForIn(for_in_statement) => self.visit_for_in_stmt(for_in_statement),
             stmt::Stmt::If(if_statement) => self.visit_if_stmt(if_statement),
             stmt::Stmt::Print(print_statement) => self.visit_print_stmt(print_statement),
             stmt::Stmt::Var(var_statement) => self.visit_var_stmt(var_statement),
             stmt::Stmt::While(while_statement) => self.visit_while_stmt(while_statement),
+        };
+        if nests {
+            self.depth -= 1;
         }
+        result
     }
 }
 
 type Result<T> = std::result::Result<T, InterpreterError>;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::environment::Environment;
+    use super::Interpreter;
+    use crate::diagnostic::Span;
+    use crate::error::SimpleErrorHandler;
+    use crate::parser::{expr, stmt};
+
+    fn span() -> Span {
+        Span {
+            line: 1,
+            column: 1,
+            start: 0,
+            end: 1,
+        }
+    }
+
+    #[test]
+    fn error_statements_are_runtime_errors() {
+        let mut error_handler = SimpleErrorHandler::new();
+        let statements = vec![stmt::Stmt::Error(stmt::Error { span: span() })];
+        Interpreter::new(
+            &mut error_handler,
+            Rc::new(RefCell::new(Environment::new())),
+        )
+        .interpret(&statements);
+        assert!(error_handler.had_runtime_error);
+    }
+
+    #[test]
+    fn error_expressions_are_runtime_errors() {
+        let mut error_handler = SimpleErrorHandler::new();
+        let statements = vec![stmt::Stmt::Print(stmt::Print {
+            expression: Box::new(expr::Expr::Error(expr::Error { span: span() })),
+        })];
+        Interpreter::new(
+            &mut error_handler,
+            Rc::new(RefCell::new(Environment::new())),
+        )
+        .interpret(&statements);
+        assert!(error_handler.had_runtime_error);
+    }
+}
@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::super::environment::Environment;
+use super::super::value::Value;
+use super::{define, string_arg};
+
+/// Host-side limits on what the file natives may touch. An empty
+/// `allowed_roots` disables file access entirely.
+#[derive(Clone)]
+pub struct FsPolicy {
+    pub allowed_roots: Vec<PathBuf>,
+    pub read_only: bool,
+}
+
+impl FsPolicy {
+    pub fn disabled() -> Self {
+        Self {
+            allowed_roots: Vec::new(),
+            read_only: true,
+        }
+    }
+
+    /// Read-write access to the current working directory, as the CLI allows
+    pub fn working_directory() -> Self {
+        Self {
+            allowed_roots: vec![PathBuf::from(".")],
+            read_only: false,
+        }
+    }
+
+    /// Resolves `path` to an absolute path inside one of the allowed roots.
+    /// Paths that don't exist yet are resolved through their parent directory.
+    /// Dangling symlinks are refused, since writing through one would create its
+    /// target wherever it points.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        if self.allowed_roots.is_empty() {
+            return Err(String::from("File access is disabled."));
+        }
+        let requested = Path::new(path);
+        let resolved = match requested.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => {
+                let is_link = fs::symlink_metadata(requested)
+                    .map(|metadata| metadata.file_type().is_symlink())
+                    .unwrap_or(false);
+                if is_link {
+                    return Err(format!("Access to '{}' is not allowed.", path));
+                }
+                let name = requested
+                    .file_name()
+                    .ok_or_else(|| format!("Invalid path '{}'.", path))?;
+                let parent = match requested.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                parent
+                    .canonicalize()
+                    .map_err(|err| format!("Cannot resolve '{}': {}.", path, err))?
+                    .join(name)
+            }
+        };
+        let allowed = self
+            .allowed_roots
+            .iter()
+            .filter_map(|root| root.canonicalize().ok())
+            .any(|root| resolved.starts_with(root));
+        if allowed {
+            Ok(resolved)
+        } else {
+            Err(format!("Access to '{}' is not allowed.", path))
+        }
+    }
+}
+
+impl Default for FsPolicy {
+    /// No file access, so embedders have to opt in with `Lox::set_fs_policy`
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+pub fn define_all(environment: &mut Environment, policy: Rc<RefCell<FsPolicy>>) {
+    let read_policy = policy.clone();
+    define(environment, "readFile", 1, move |args| {
+        let path = string_arg(args, 0)?;
+        let resolved = read_policy.borrow().resolve(path)?;
+        fs::read_to_string(resolved)
+            .map(Value::String)
+            .map_err(|err| format!("Cannot read '{}': {}.", path, err))
+    });
+
+    let write_policy = policy.clone();
+    define(environment, "writeFile", 2, move |args| {
+        let path = string_arg(args, 0)?;
+        let contents = string_arg(args, 1)?;
+        let policy = write_policy.borrow();
+        if policy.read_only {
+            return Err(format!(
+                "Cannot write '{}': file access is read-only.",
+                path
+            ));
+        }
+        fs::write(policy.resolve(path)?, contents)
+            .map(|_| Value::Nil)
+            .map_err(|err| format!("Cannot write '{}': {}.", path, err))
+    });
+
+    define(environment, "fileExists", 1, move |args| {
+        let path = string_arg(args, 0)?;
+        let resolved = policy.borrow().resolve(path)?;
+        Ok(Value::Boolean(resolved.exists()))
+    });
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use super::{define_all, FsPolicy};
+    use crate::interpreter::environment::Environment;
+    use crate::interpreter::value::Value;
+
+    /// A fresh directory under the system temp dir, with `root` inside it for the
+    /// sandbox and room beside it for files the sandbox must not reach
+    fn temp_dir(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("rlox-fs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        (dir, root)
+    }
+
+    fn read_write(root: &Path) -> FsPolicy {
+        FsPolicy {
+            allowed_roots: vec![root.to_path_buf()],
+            read_only: false,
+        }
+    }
+
+    fn write_file(policy: FsPolicy, path: &Path, contents: &str) -> Result<Value, String> {
+        let mut environment = Environment::new();
+        define_all(&mut environment, Rc::new(RefCell::new(policy)));
+        let write = environment
+            .values()
+            .into_iter()
+            .find_map(|(name, value)| match value {
+                Value::NativeFunction(function) if name == "writeFile" => Some(function),
+                _ => None,
+            })
+            .unwrap();
+        write.call(&[
+            Value::String(path.to_string_lossy().into_owned()),
+            Value::String(String::from(contents)),
+        ])
+    }
+
+    #[test]
+    fn writes_new_files_inside_the_root() {
+        let (dir, root) = temp_dir("inside");
+        let path = root.join("new.txt");
+        assert!(write_file(read_write(&root), &path, "ok").is_ok());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ok");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_root() {
+        let (dir, root) = temp_dir("outside");
+        let path = root.join("..").join("escaped.txt");
+        assert!(write_file(read_write(&root), &path, "no").is_err());
+        assert!(!dir.join("escaped.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_dangling_symlinks_out_of_the_root() {
+        let (dir, root) = temp_dir("dangling");
+        let target = dir.join("escaped_target");
+        let link = root.join("dangling");
+        symlink(&target, &link).unwrap();
+        assert!(write_file(read_write(&root), &link, "pwned").is_err());
+        assert!(!target.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_symlinks_to_existing_files_out_of_the_root() {
+        let (dir, root) = temp_dir("existing");
+        let target = dir.join("outside.txt");
+        fs::write(&target, "original").unwrap();
+        let link = root.join("link");
+        symlink(&target, &link).unwrap();
+        assert!(write_file(read_write(&root), &link, "pwned").is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "original");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,48 @@
+use std::f64::consts;
+
+use super::super::environment::Environment;
+use super::super::value::Value;
+use super::{define, number_arg};
+
+pub fn define_all(environment: &mut Environment) {
+    environment.define("PI", Value::Number(consts::PI));
+    environment.define("E", Value::Number(consts::E));
+
+    define_unary(environment, "sqrt", f64::sqrt);
+    define_unary(environment, "floor", f64::floor);
+    define_unary(environment, "ceil", f64::ceil);
+    define_unary(environment, "round", f64::round);
+    define_unary(environment, "abs", f64::abs);
+    define_unary(environment, "sin", f64::sin);
+    define_unary(environment, "cos", f64::cos);
+    define_unary(environment, "tan", f64::tan);
+    define_unary(environment, "asin", f64::asin);
+    define_unary(environment, "acos", f64::acos);
+    define_unary(environment, "atan", f64::atan);
+    define_binary(environment, "atan2", f64::atan2);
+    define_binary(environment, "pow", f64::powf);
+    define_binary(environment, "min", f64::min);
+    define_binary(environment, "max", f64::max);
+
+    define(environment, "isNaN", 1, |args| {
+        Ok(Value::Boolean(number_arg(args, 0)?.is_nan()))
+    });
+    define(environment, "isInfinite", 1, |args| {
+        Ok(Value::Boolean(number_arg(args, 0)?.is_infinite()))
+    });
+}
+
+fn define_unary(environment: &mut Environment, name: &'static str, function: fn(f64) -> f64) {
+    define(environment, name, 1, move |args| {
+        Ok(Value::Number(function(number_arg(args, 0)?)))
+    });
+}
+
+fn define_binary(environment: &mut Environment, name: &'static str, function: fn(f64, f64) -> f64) {
+    define(environment, name, 2, move |args| {
+        Ok(Value::Number(function(
+            number_arg(args, 0)?,
+            number_arg(args, 1)?,
+        )))
+    });
+}
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::environment::Environment;
+use super::value::Value;
+mod fs;
+mod math;
+mod string;
+
+pub use fs::FsPolicy;
+
+/// Natives report failures as a bare message; the interpreter attaches the call site
+pub type NativeResult = std::result::Result<Value, String>;
+
+type NativeFn = dyn Fn(&[Value]) -> NativeResult;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &'static str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> NativeResult + 'static,
+    ) -> Self {
+        Self {
+            name,
+            arity,
+            function: Rc::new(function),
+        }
+    }
+
+    pub fn call(&self, arguments: &[Value]) -> NativeResult {
+        (self.function)(arguments)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+pub fn define_globals(environment: &mut Environment, fs_policy: Rc<RefCell<FsPolicy>>) {
+    fs::define_all(environment, fs_policy);
+    math::define_all(environment);
+    string::define_all(environment);
+}
+
+fn define(
+    environment: &mut Environment,
+    name: &'static str,
+    arity: usize,
+    function: impl Fn(&[Value]) -> NativeResult + 'static,
+) {
+    environment.define(
+        name,
+        Value::NativeFunction(NativeFunction::new(name, arity, function)),
+    );
+}
+
+fn string_arg(arguments: &[Value], index: usize) -> std::result::Result<&str, String> {
+    match &arguments[index] {
+        Value::String(string) => Ok(string),
+        other => Err(format!(
+            "Expected a string as argument {} but got '{}'.",
+            index + 1,
+            other
+        )),
+    }
+}
+
+fn number_arg(arguments: &[Value], index: usize) -> std::result::Result<f64, String> {
+    match &arguments[index] {
+        Value::Number(num) => Ok(*num),
+        other => Err(format!(
+            "Expected a number as argument {} but got '{}'.",
+            index + 1,
+            other
+        )),
+    }
+}
@@ -0,0 +1,60 @@
+//! String natives. Lengths and positions count Unicode scalar values, the same
+//! unit the scanner and string slicing use.
+
+use super::super::environment::Environment;
+use super::super::value::{Range, Value};
+use super::{define, number_arg, string_arg};
+
+pub fn define_all(environment: &mut Environment) {
+    define(environment, "len", 1, |args| {
+        Ok(Value::Number(string_arg(args, 0)?.chars().count() as f64))
+    });
+    define(environment, "substring", 3, |args| {
+        let chars: Vec<char> = string_arg(args, 0)?.chars().collect();
+        let range = Range {
+            start: number_arg(args, 1)?,
+            end: number_arg(args, 2)?,
+            inclusive: false,
+        };
+        let (start, end) = range.bounds(chars.len()).ok_or_else(|| {
+            format!(
+                "Substring {} out of bounds for string of length {}.",
+                Value::Range(range.clone()),
+                chars.len()
+            )
+        })?;
+        Ok(Value::String(chars[start..end].iter().collect()))
+    });
+    define(environment, "indexOf", 2, |args| {
+        let string = string_arg(args, 0)?;
+        let position = match string.find(string_arg(args, 1)?) {
+            Some(byte_index) => string[..byte_index].chars().count() as f64,
+            None => -1.0,
+        };
+        Ok(Value::Number(position))
+    });
+    define(environment, "trim", 1, |args| {
+        Ok(Value::String(string_arg(args, 0)?.trim().to_string()))
+    });
+    define(environment, "upper", 1, |args| {
+        Ok(Value::String(string_arg(args, 0)?.to_uppercase()))
+    });
+    define(environment, "lower", 1, |args| {
+        Ok(Value::String(string_arg(args, 0)?.to_lowercase()))
+    });
+    define(environment, "replace", 3, |args| {
+        Ok(Value::String(
+            string_arg(args, 0)?.replace(string_arg(args, 1)?, string_arg(args, 2)?),
+        ))
+    });
+    define(environment, "startsWith", 2, |args| {
+        Ok(Value::Boolean(
+            string_arg(args, 0)?.starts_with(string_arg(args, 1)?),
+        ))
+    });
+    define(environment, "endsWith", 2, |args| {
+        Ok(Value::Boolean(
+            string_arg(args, 0)?.ends_with(string_arg(args, 1)?),
+        ))
+    });
+}
@@ -0,0 +1,122 @@
+/// Picks the candidate closest to `name` by edit distance, if any is close
+/// enough to plausibly be a typo. Names shorter than three chars never get a
+/// suggestion since almost anything is one edit away. Ties go to the
+/// alphabetically first name.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = name.chars().count() / 3;
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The message suffix for a suggestion, or an empty string
+pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    match closest(name, candidates) {
+        Some(suggestion) => format!(" Did you mean '{}'?", suggestion),
+        None => String::new(),
+    }
+}
+
+/// Optimal string alignment distance, counted in chars: Levenshtein plus
+/// swaps of two adjacent chars, so `coutn` is one edit from `count`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_previous: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1];
+        for j in 0..b.len() {
+            let substitution = previous[j] + if a[i] == b[j] { 0 } else { 1 };
+            let mut distance = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                distance = distance.min(before_previous[j - 1] + 1);
+            }
+            current.push(distance);
+        }
+        before_previous = previous;
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("count", "cont"), 1);
+        assert_eq!(edit_distance("cont", "count"), 1);
+    }
+
+    #[test]
+    fn edit_distance_counts_a_swap_as_one_edit() {
+        assert_eq!(edit_distance("coutn", "count"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        assert_eq!(edit_distance("abcd", "badc"), 2);
+        // Optimal string alignment never edits a swapped pair again
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn edit_distance_counts_chars_not_bytes() {
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+        assert_eq!(edit_distance("日本", "日本語"), 1);
+    }
+
+    #[test]
+    fn closest_finds_a_typo() {
+        assert_eq!(closest("lenght", vec!["length", "print"]), Some("length"));
+        assert_eq!(closest("coutn", vec!["count"]), Some("count"));
+    }
+
+    #[test]
+    fn closest_allows_one_edit_per_three_chars() {
+        assert_eq!(closest("abc", vec!["abd"]), Some("abd"));
+        assert_eq!(closest("abc", vec!["xyc"]), None);
+        assert_eq!(closest("abcdef", vec!["abcxyf"]), Some("abcxyf"));
+        assert_eq!(closest("abcdef", vec!["axyzef"]), None);
+    }
+
+    #[test]
+    fn closest_ignores_short_names() {
+        assert_eq!(closest("ab", vec!["ac", "b"]), None);
+    }
+
+    #[test]
+    fn closest_never_suggests_the_name_itself() {
+        assert_eq!(closest("count", vec!["count"]), None);
+        assert_eq!(closest("count", vec!["count", "counts"]), Some("counts"));
+    }
+
+    #[test]
+    fn closest_breaks_ties_alphabetically() {
+        assert_eq!(closest("cat", vec!["cot", "bat", "cab"]), Some("bat"));
+    }
+
+    #[test]
+    fn closest_prefers_the_smaller_distance() {
+        assert_eq!(
+            closest("abcdefgh", vec!["abxdefyh", "abcdefgx"]),
+            Some("abcdefgx")
+        );
+    }
+
+    #[test]
+    fn did_you_mean_formats_the_suggestion() {
+        assert_eq!(
+            did_you_mean("lenght", vec!["length"]),
+            " Did you mean 'length'?"
+        );
+        assert_eq!(did_you_mean("zzzzzz", vec!["length"]), "");
+    }
+}
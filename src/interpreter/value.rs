@@ -1,10 +1,19 @@
 use std::fmt::Display;
+use std::iter;
+use std::rc::Rc;
+
+use super::native::NativeFunction;
 
 #[derive(PartialEq, Clone)]
 pub enum Value {
     Number(f64),
     String(String),
     Boolean(bool),
+    Range(Range),
+    NativeFunction(NativeFunction),
+    Enum(Rc<EnumType>),
+    VariantConstructor(Rc<EnumType>, usize),
+    Variant(Rc<VariantValue>),
     Nil,
 }
 
@@ -17,7 +26,175 @@ impl Display for Value {
             }
             Value::String(string) => write!(f, "{}", string),
             Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Range(range) => write!(
+                f,
+                "{}{}{}",
+                Value::Number(range.start),
+                if range.inclusive { "..=" } else { ".." },
+                Value::Number(range.end)
+            ),
+            Value::NativeFunction(function) => write!(f, "<native fn {}>", function.name),
+            Value::Enum(enum_type) => write!(f, "<enum {}>", enum_type.name),
+            Value::VariantConstructor(enum_type, variant) => write!(
+                f,
+                "<constructor {}.{}>",
+                enum_type.name, enum_type.variants[*variant].name
+            ),
+            Value::Variant(value) => {
+                let variant = &value.enum_type.variants[value.variant];
+                write!(f, "{}.{}", value.enum_type.name, variant.name)?;
+                if let Some(fields) = &variant.fields {
+                    write!(f, "(")?;
+                    for (i, (name, field)) in fields.iter().zip(&value.fields).enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}: {}", name, field)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
             Value::Nil => write!(f, "nil"),
         }
     }
 }
+
+#[derive(PartialEq, Clone)]
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    /// Counts up from `start` in steps of one, producing each number only when asked for.
+    /// Stops early past 2^53, where adding one no longer changes the number.
+    pub fn iter(&self) -> impl Iterator<Item = f64> {
+        let (end, inclusive) = (self.end, self.inclusive);
+        iter::successors(Some(self.start), |i| Some(i + 1.0).filter(|next| next > i))
+            .take_while(move |i| *i < end || (inclusive && *i == end))
+    }
+
+    /// Converts the range into `start..end` indices into a sequence of length `len`,
+    /// or `None` if the bounds are fractional, negative or past the end
+    pub fn bounds(&self, len: usize) -> Option<(usize, usize)> {
+        let end = if self.inclusive {
+            self.end + 1.0
+        } else {
+            self.end
+        };
+        if self.start.fract() != 0.0 || end.fract() != 0.0 {
+            return None;
+        }
+        if self.start < 0.0 || self.start > end || end > len as f64 {
+            return None;
+        }
+        Some((self.start as usize, end as usize))
+    }
+}
+
+/// A declared `enum`. Enums compare by identity, so two declarations with
+/// the same shape produce distinct, unequal types.
+pub struct EnumType {
+    pub name: String,
+    pub variants: Vec<VariantDef>,
+}
+
+impl EnumType {
+    pub fn variant(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .position(|variant| variant.name == name)
+    }
+}
+
+impl PartialEq for EnumType {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// `fields` is `None` for unit variants, which are values rather than constructors
+pub struct VariantDef {
+    pub name: String,
+    pub fields: Option<Vec<String>>,
+}
+
+#[derive(PartialEq)]
+pub struct VariantValue {
+    pub enum_type: Rc<EnumType>,
+    pub variant: usize,
+    pub fields: Vec<Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Range;
+
+    fn range(start: f64, end: f64, inclusive: bool) -> Range {
+        Range {
+            start,
+            end,
+            inclusive,
+        }
+    }
+
+    #[test]
+    fn iter_counts_up_to_the_end() {
+        let numbers: Vec<f64> = range(0.0, 3.0, false).iter().collect();
+        assert_eq!(numbers, vec![0.0, 1.0, 2.0]);
+        let numbers: Vec<f64> = range(0.0, 3.0, true).iter().collect();
+        assert_eq!(numbers, vec![0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn iter_steps_from_a_fractional_start() {
+        let numbers: Vec<f64> = range(0.5, 2.0, true).iter().collect();
+        assert_eq!(numbers, vec![0.5, 1.5]);
+    }
+
+    #[test]
+    fn iter_is_empty_for_backwards_ranges() {
+        assert_eq!(range(3.0, 1.0, false).iter().count(), 0);
+        assert_eq!(range(1.0, 1.0, false).iter().count(), 0);
+        assert_eq!(range(1.0, 1.0, true).iter().count(), 1);
+    }
+
+    #[test]
+    fn iter_stops_where_adding_one_no_longer_counts() {
+        // 2^53 + 1 rounds back down to 2^53
+        let start = 9007199254740992.0;
+        let numbers: Vec<f64> = range(start, start + 2.0, false).iter().collect();
+        assert_eq!(numbers, vec![start]);
+        assert_eq!(range(f64::NEG_INFINITY, 0.0, false).iter().count(), 1);
+    }
+
+    #[test]
+    fn bounds_converts_to_indices() {
+        assert_eq!(range(1.0, 3.0, false).bounds(5), Some((1, 3)));
+        assert_eq!(range(1.0, 3.0, true).bounds(5), Some((1, 4)));
+        assert_eq!(range(0.0, 5.0, false).bounds(5), Some((0, 5)));
+        assert_eq!(range(2.0, 2.0, false).bounds(5), Some((2, 2)));
+    }
+
+    #[test]
+    fn bounds_rejects_fractions() {
+        assert_eq!(range(0.5, 3.0, false).bounds(5), None);
+        assert_eq!(range(0.0, 2.5, true).bounds(5), None);
+    }
+
+    #[test]
+    fn bounds_rejects_negative_and_backwards_ranges() {
+        assert_eq!(range(-1.0, 3.0, false).bounds(5), None);
+        assert_eq!(range(3.0, 1.0, false).bounds(5), None);
+    }
+
+    #[test]
+    fn bounds_rejects_ends_past_the_sequence() {
+        assert_eq!(range(0.0, 6.0, false).bounds(5), None);
+        // An inclusive end of 5 reaches index 5, which is one past the end
+        assert_eq!(range(0.0, 5.0, true).bounds(5), None);
+        assert_eq!(range(0.0, 4.0, true).bounds(5), Some((0, 5)));
+    }
+}
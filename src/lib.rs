@@ -1,11 +1,25 @@
+mod checker;
+pub mod diagnostic;
 mod error;
+mod formatter;
 pub mod interpreter;
+mod json;
+mod lint;
+mod lsp;
 mod parser;
 mod scanner;
+use checker::Checker;
+use diagnostic::{DiagnosticSink, Span};
 use error::SimpleErrorHandler;
+use formatter::Formatter;
+use interpreter::debugger::Debugger;
 use interpreter::environment::Environment;
+use interpreter::native::{self, FsPolicy};
 use interpreter::Interpreter;
-use parser::Parser;
+use lint::Linter;
+use parser::printer::AstPrinter;
+use parser::{stmt, Parser};
+use scanner::token_type::TokenType;
 use scanner::Scanner;
 use std::{
     cell::RefCell,
@@ -15,21 +29,157 @@ use std::{
     rc::Rc,
 };
 
+const PRELUDE: &str = include_str!("prelude.lox");
+const PRELUDE_NAME: &str = "<prelude>";
+
 pub struct Lox {
     error_handler: SimpleErrorHandler,
     environment: Rc<RefCell<Environment>>,
+    fs_policy: Rc<RefCell<FsPolicy>>,
+    /// Whether the prelude still has to run before the first script
+    prelude_pending: bool,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lox {
+    /// Creates an interpreter with the natives loaded. The Lox prelude runs just before
+    /// the first script, so it sees any sink or policy set in the meantime.
     pub fn new() -> Self {
+        Self {
+            prelude_pending: true,
+            ..Self::without_prelude()
+        }
+    }
+
+    /// Creates an interpreter with only the natives, for minimal embeddings
+    pub fn without_prelude() -> Self {
+        let fs_policy = Rc::new(RefCell::new(FsPolicy::default()));
+        let mut environment = Environment::new();
+        native::define_globals(&mut environment, fs_policy.clone());
         Self {
             error_handler: SimpleErrorHandler::new(),
-            environment: Rc::new(RefCell::new(Environment::new())),
+            environment: Rc::new(RefCell::new(environment)),
+            fs_policy,
+            prelude_pending: false,
         }
     }
+
+    /// Restricts which files scripts can reach through the file natives
+    pub fn set_fs_policy(&mut self, policy: FsPolicy) {
+        *self.fs_policy.borrow_mut() = policy;
+    }
+
+    /// Routes scanner, parser, checker and runtime diagnostics to `sink` instead of stderr
+    pub fn set_diagnostic_sink(&mut self, sink: Box<dyn DiagnosticSink>) {
+        self.error_handler.set_sink(sink);
+    }
+
     pub fn run_file(&mut self, path: &str) -> io::Result<()> {
         let source = fs::read_to_string(path)?;
-        self.run(&source);
+        self.load_prelude();
+        if let Some(statements) = self.parse(&source) {
+            Linter::new(&mut self.error_handler).lint(&statements);
+            self.interpret(&statements);
+        }
+        if self.error_handler.had_error {
+            process::exit(65);
+        }
+        if self.error_handler.had_runtime_error {
+            process::exit(70);
+        }
+        Ok(())
+    }
+
+    /// Type checks and lints a file without running it
+    pub fn check_file(&mut self, path: &str) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        if let Some(statements) = self.parse(&source) {
+            Checker::new(&mut self.error_handler).check(&statements);
+            Linter::new(&mut self.error_handler).lint(&statements);
+        }
+        if self.error_handler.had_error {
+            process::exit(65);
+        }
+        Ok(())
+    }
+
+    /// Prints the syntax tree of a file as S-expressions without running it. The tree
+    /// is printed even if there were parse errors, with the broken parts shown as `(error)`.
+    pub fn print_ast(&mut self, path: &str) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        let statements = self.syntax_tree(&source);
+        print!("{}", AstPrinter::print(&statements));
+        if self.error_handler.had_error {
+            process::exit(65);
+        }
+        Ok(())
+    }
+
+    /// Prints every token in a file, one per line, in the `Token` display format or as
+    /// JSON objects
+    pub fn print_tokens(&mut self, path: &str, json: bool) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        self.error_handler.set_source(&source);
+        let mut scanner = Scanner::new(&source, &mut self.error_handler);
+        for token in scanner.scan_tokens() {
+            if json {
+                println!("{}", token.to_json());
+            } else {
+                println!("{}", token);
+            }
+        }
+        if self.error_handler.had_error {
+            process::exit(65);
+        }
+        Ok(())
+    }
+
+    /// Rewrites a file in the canonical format. With `check`, leaves the file alone
+    /// and exits with status 1 if it isn't already formatted.
+    pub fn format_file(&mut self, path: &str, check: bool) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        self.error_handler.set_source(&source);
+        let tokens = Scanner::with_comments(&source, &mut self.error_handler).scan_tokens();
+        // Refuse to format code that doesn't parse, where the layout may be what's wrong
+        let code = tokens
+            .iter()
+            .filter(|token| token.token_type != TokenType::Comment)
+            .cloned()
+            .collect();
+        Parser::new(code, &mut self.error_handler).parse();
+        if self.error_handler.had_error {
+            process::exit(65);
+        }
+
+        let formatted = Formatter::format(&tokens);
+        if formatted == source {
+            return Ok(());
+        }
+        if check {
+            self.error_handler.format_error(
+                first_difference(&source, &formatted),
+                &format!("{} is not formatted.", path),
+            );
+            process::exit(1);
+        }
+        fs::write(path, formatted)
+    }
+
+    /// Runs a file under the interactive debugger, paused before the first statement
+    pub fn debug_file(&mut self, path: &str) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        self.load_prelude();
+        if let Some(statements) = self.parse(&source) {
+            let mut interpreter =
+                Interpreter::new(&mut self.error_handler, self.environment.clone());
+            interpreter.set_debugger(Debugger::new(&source));
+            interpreter.interpret(&statements);
+        }
         if self.error_handler.had_error {
             process::exit(65);
         }
@@ -39,6 +189,14 @@ impl Lox {
         Ok(())
     }
 
+    /// Serves the Language Server Protocol over stdin and stdout until the client exits
+    pub fn run_language_server(&mut self) -> io::Result<()> {
+        self.load_prelude();
+        let builtins = self.environment.borrow().bindings();
+        let code = lsp::Server::new(builtins).run(io::stdin().lock(), io::stdout().lock())?;
+        process::exit(code);
+    }
+
     pub fn run_prompt(&mut self) -> io::Result<()> {
         println!("Running rlox prompt");
         let mut buf = String::new();
@@ -56,14 +214,91 @@ impl Lox {
     }
 
     pub fn run(&mut self, source: &str) {
+        self.load_prelude();
+        if let Some(statements) = self.parse(source) {
+            self.interpret(&statements);
+        }
+    }
+
+    /// Runs the prelude if it hasn't run yet. Its errors are reported under
+    /// `<prelude>` rather than the name of the script that triggered it.
+    fn load_prelude(&mut self) {
+        if !self.prelude_pending {
+            return;
+        }
+        self.prelude_pending = false;
+        let source_name = self.error_handler.source_name.take();
+        self.error_handler.source_name = Some(String::from(PRELUDE_NAME));
+        if let Some(statements) = self.parse(PRELUDE) {
+            self.interpret(&statements);
+        }
+        self.error_handler.source_name = source_name;
+    }
+
+    /// Scans and parses `source`, returning `None` if there were any errors
+    fn parse(&mut self, source: &str) -> Option<Vec<stmt::Stmt>> {
+        let statements = self.syntax_tree(source);
+        if self.error_handler.had_error {
+            return None;
+        };
+        Some(statements)
+    }
+
+    /// Scans and parses `source`, keeping anything that failed to parse as error nodes
+    fn syntax_tree(&mut self, source: &str) -> Vec<stmt::Stmt> {
+        self.error_handler.set_source(source);
         let mut scanner = Scanner::new(source, &mut self.error_handler);
         let tokens = scanner.scan_tokens();
         let mut parser = Parser::new(tokens, &mut self.error_handler);
-        let statements = parser.parse();
-        if self.error_handler.had_error {
-            return;
-        };
+        parser.parse()
+    }
+
+    fn interpret(&mut self, statements: &[stmt::Stmt]) {
         let mut interpreter = Interpreter::new(&mut self.error_handler, self.environment.clone());
-        interpreter.interpret(&statements);
+        interpreter.interpret(statements);
+    }
+}
+
+/// Span of the whole line in `source` holding the first char the formatter changed
+fn first_difference(source: &str, formatted: &str) -> Span {
+    let chars: Vec<char> = source.chars().collect();
+    let differs = chars
+        .iter()
+        .zip(formatted.chars())
+        .position(|(a, b)| *a != b)
+        .unwrap_or_else(|| chars.len().min(formatted.chars().count()));
+    let line_start = chars[..differs]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let line_end = chars[differs..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |i| differs + i);
+    Span {
+        line: chars[..differs].iter().filter(|c| **c == '\n').count() as u32 + 1,
+        column: 1,
+        start: line_start as u32,
+        end: line_end as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::first_difference;
+
+    #[test]
+    fn first_difference_spans_the_first_changed_line() {
+        let span = first_difference("var a = 1;\nprint   a;\n", "var a = 1;\nprint a;\n");
+        assert_eq!(
+            (span.line, span.column, span.start, span.end),
+            (2, 1, 11, 21)
+        );
+    }
+
+    #[test]
+    fn first_difference_handles_a_missing_final_newline() {
+        let span = first_difference("print 1;", "print 1;\n");
+        assert_eq!((span.line, span.start, span.end), (1, 0, 8));
     }
 }
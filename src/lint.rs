@@ -0,0 +1,342 @@
+use crate::diagnostic::{codes, Span};
+use crate::error::SimpleErrorHandler;
+use crate::parser::{expr, stmt};
+use crate::scanner::token::{Literal, Token};
+
+struct Declaration {
+    name: Token,
+    read: bool,
+}
+
+/// Static pass that reports likely mistakes as warnings. Warnings never set
+/// `had_error`, so linting can't stop a script from running.
+pub struct Linter<'a> {
+    error_handler: &'a mut SimpleErrorHandler,
+    /// Innermost scope last. The first scope holds the file's globals, which
+    /// may be read by later REPL lines or other code, so they are never unused.
+    scopes: Vec<Vec<Declaration>>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(error_handler: &'a mut SimpleErrorHandler) -> Self {
+        Self {
+            error_handler,
+            scopes: vec![Vec::new()],
+        }
+    }
+
+    pub fn lint(&mut self, statements: &[stmt::Stmt]) {
+        for statement in statements {
+            self.lint_stmt(statement);
+        }
+    }
+
+    fn visit_assign_expr(&mut self, expr: &expr::Assign) {
+        if let expr::Expr::Variable(variable) = &*expr.value {
+            if variable.name.lexeme == expr.name.lexeme {
+                self.warn(
+                    codes::SELF_ASSIGNMENT,
+                    Span::from(&expr.name).to(Span::from(&variable.name)),
+                    &format!("'{}' is assigned to itself.", expr.name.lexeme),
+                );
+                return;
+            }
+        }
+        self.lint_expr(&expr.value);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &expr::Variable) {
+        let declaration = self.scopes.iter_mut().rev().find_map(|scope| {
+            scope
+                .iter_mut()
+                .rev()
+                .find(|declaration| declaration.name.lexeme == expr.name.lexeme)
+        });
+        if let Some(declaration) = declaration {
+            declaration.read = true;
+        }
+    }
+
+    fn lint_expr(&mut self, expr: &expr::Expr) {
+        match expr {
+            expr::Expr::Assign(assign) => self.visit_assign_expr(assign),
+            expr::Expr::Binary(binary) => {
+                self.lint_expr(&binary.left);
+                self.lint_expr(&binary.right);
+            }
+            expr::Expr::Call(call) => {
+                self.lint_expr(&call.callee);
+                for argument in &call.arguments {
+                    self.lint_expr(argument);
+                }
+            }
+            expr::Expr::Error(_) => (),
+            expr::Expr::Get(get) => self.lint_expr(&get.object),
+            expr::Expr::Grouping(grouping) => self.lint_expr(&grouping.expression),
+            expr::Expr::Index(index) => {
+                self.lint_expr(&index.object);
+                self.lint_expr(&index.index);
+            }
+            expr::Expr::Literal(_) => (),
+            expr::Expr::Logical(logical) => {
+                self.lint_expr(&logical.left);
+                self.lint_expr(&logical.right);
+            }
+            expr::Expr::Range(range) => {
+                self.lint_expr(&range.start);
+                self.lint_expr(&range.end);
+            }
+            expr::Expr::Unary(unary) => self.lint_expr(&unary.right),
+            expr::Expr::Variable(variable) => self.visit_variable_expr(variable),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &stmt::Block) {
+        self.begin_scope();
+        self.lint(&stmt.statements);
+        self.end_scope();
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &stmt::ForIn) {
+        self.lint_expr(&stmt.iterable);
+        self.begin_scope();
+        self.declare(&stmt.name);
+        self.lint_stmt(&stmt.body);
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &stmt::If) {
+        self.check_condition(&stmt.condition, false);
+        self.lint_stmt(&stmt.then_branch);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.lint_stmt(else_branch);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &stmt::Var) {
+        self.lint_expr(&stmt.initializer);
+        self.declare(&stmt.name);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &stmt::While) {
+        self.check_condition(&stmt.condition, true);
+        self.lint_stmt(&stmt.body);
+    }
+
+    fn lint_stmt(&mut self, statement: &stmt::Stmt) {
+        match statement {
+            stmt::Stmt::Assert(assert_statement) => {
+                self.lint_expr(&assert_statement.condition);
+                if let Some(message) = &assert_statement.message {
+                    self.lint_expr(message);
+                }
+            }
+            stmt::Stmt::Block(block_statement) => self.visit_block_stmt(block_statement),
+            stmt::Stmt::Enum(enum_statement) => self.declare(&enum_statement.name),
+            stmt::Stmt::Error(_) => (),
+            stmt::Stmt::Expression(expression_statement) => {
+                self.lint_expr(&expression_statement.expression)
+            }
+            stmt::Stmt::ForIn(for_in_statement) => self.visit_for_in_stmt(for_in_statement),
+            stmt::Stmt::If(if_statement) => self.visit_if_stmt(if_statement),
+            stmt::Stmt::Print(print_statement) => self.lint_expr(&print_statement.expression),
+            stmt::Stmt::Var(var_statement) => self.visit_var_stmt(var_statement),
+            stmt::Stmt::While(while_statement) => self.visit_while_stmt(while_statement),
+        }
+    }
+
+    /// Flags `if`/`while` conditions that are a bare literal. `while (true)` is the
+    /// idiomatic infinite loop, so it is allowed.
+    fn check_condition(&mut self, condition: &expr::Expr, is_loop: bool) {
+        self.lint_expr(condition);
+
+        let mut inner = condition;
+        while let expr::Expr::Grouping(grouping) = inner {
+            inner = &grouping.expression;
+        }
+        // Literals without a span were synthesized by the parser, e.g. `for (;;)`
+        if let expr::Expr::Literal(expr::Literal {
+            value,
+            span: Some(span),
+        }) = inner
+        {
+            let truthy = !matches!(**value, Literal::Nil | Literal::False);
+            if is_loop && matches!(**value, Literal::True) {
+                return;
+            }
+            self.warn(
+                codes::CONSTANT_CONDITION,
+                *span,
+                &format!(
+                    "Condition is always {}.",
+                    if truthy { "truthy" } else { "falsey" }
+                ),
+            );
+        }
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if self.scopes.len() > 1 {
+            let shadows = self.scopes[..self.scopes.len() - 1]
+                .iter()
+                .flatten()
+                .any(|declaration| declaration.name.lexeme == name.lexeme);
+            if shadows {
+                self.warn(
+                    codes::SHADOWED_VARIABLE,
+                    Span::from(name),
+                    &format!("'{}' shadows a variable from an outer scope.", name.lexeme),
+                );
+            }
+        }
+        self.scopes.last_mut().unwrap().push(Declaration {
+            name: name.clone(),
+            read: false,
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        for declaration in scope {
+            // A leading underscore marks a variable as intentionally unused
+            if !declaration.read && !declaration.name.lexeme.starts_with('_') {
+                self.warn(
+                    codes::UNUSED_VARIABLE,
+                    Span::from(&declaration.name),
+                    &format!("'{}' is declared but never read.", declaration.name.lexeme),
+                );
+            }
+        }
+    }
+
+    fn warn(&mut self, code: &'static str, span: Span, msg: &str) {
+        self.error_handler.warning(code, span, msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::Linter;
+    use crate::diagnostic::{codes, Diagnostic, DiagnosticSink, Severity};
+    use crate::error::SimpleErrorHandler;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    struct Collector(Rc<RefCell<Vec<Diagnostic>>>);
+
+    impl DiagnosticSink for Collector {
+        fn report(&mut self, diagnostic: Diagnostic, _source: &str) {
+            self.0.borrow_mut().push(diagnostic);
+        }
+    }
+
+    /// Parses and lints `source`, returning the code and message of each warning
+    fn lint(source: &str) -> Vec<(&'static str, String)> {
+        let diagnostics = Rc::new(RefCell::new(Vec::new()));
+        let mut error_handler = SimpleErrorHandler::new();
+        error_handler.set_sink(Box::new(Collector(diagnostics.clone())));
+        error_handler.set_source(source);
+        let tokens = Scanner::new(source, &mut error_handler).scan_tokens();
+        let statements = Parser::new(tokens, &mut error_handler).parse();
+        assert!(!error_handler.had_error, "{:?} doesn't parse", source);
+        Linter::new(&mut error_handler).lint(&statements);
+        assert!(!error_handler.had_error);
+        diagnostics
+            .take()
+            .into_iter()
+            .map(|diagnostic| {
+                assert!(matches!(diagnostic.severity, Severity::Warning));
+                (diagnostic.code, diagnostic.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_unused_locals() {
+        assert_eq!(
+            lint("{ var a = 1; var b = 2; print b; }"),
+            vec![(
+                codes::UNUSED_VARIABLE,
+                "'a' is declared but never read.".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn never_reports_unused_globals() {
+        assert!(lint("var a = 1;").is_empty());
+    }
+
+    #[test]
+    fn underscore_prefix_opts_out_of_unused_warnings() {
+        assert!(lint("{ var _a = 1; }").is_empty());
+        assert!(lint("for (var _i in 0..3) print 1;").is_empty());
+    }
+
+    #[test]
+    fn reports_shadowing() {
+        assert_eq!(
+            lint("var a = 1; { var a = 2; print a; }"),
+            vec![(
+                codes::SHADOWED_VARIABLE,
+                "'a' shadows a variable from an outer scope.".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn redeclaring_a_global_is_not_shadowing() {
+        assert!(lint("var a = 1; var a = 2;").is_empty());
+    }
+
+    #[test]
+    fn reports_self_assignment() {
+        assert_eq!(
+            lint("var a = 1; a = a;"),
+            vec![(
+                codes::SELF_ASSIGNMENT,
+                "'a' is assigned to itself.".to_string()
+            )]
+        );
+        assert!(lint("var a = 1; var b = 2; a = b;").is_empty());
+    }
+
+    #[test]
+    fn reports_constant_conditions() {
+        assert_eq!(
+            lint("if (true) print 1;\nif ((nil)) print 2;\nwhile (false) print 3;"),
+            vec![
+                (
+                    codes::CONSTANT_CONDITION,
+                    "Condition is always truthy.".to_string()
+                ),
+                (
+                    codes::CONSTANT_CONDITION,
+                    "Condition is always falsey.".to_string()
+                ),
+                (
+                    codes::CONSTANT_CONDITION,
+                    "Condition is always falsey.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn allows_infinite_loops() {
+        assert!(lint("while (true) print 1;").is_empty());
+        assert!(lint("for (;;) print 1;").is_empty());
+    }
+
+    #[test]
+    fn ignores_conditions_that_are_not_literals() {
+        assert!(lint("var a = true; if (a) print 1; while (a == false) print 2;").is_empty());
+    }
+}
@@ -0,0 +1,378 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::checker::Checker;
+use crate::diagnostic::{Diagnostic, DiagnosticSink, Span};
+use crate::error::SimpleErrorHandler;
+use crate::lint::Linter;
+use crate::parser::{expr, stmt, Parser};
+use crate::scanner::token::Token;
+use crate::scanner::token_type::TokenType;
+use crate::scanner::Scanner;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DeclarationKind {
+    Variable,
+    LoopVariable,
+    Enum,
+    Variant,
+}
+
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    /// Span of the name where it is declared
+    pub span: Span,
+    /// Declaration as written, e.g. `var x: Number` or `Shape.Circle(r)`
+    pub detail: String,
+    /// Declared at the top level of the file
+    pub global: bool,
+    /// Enum that a variant belongs to
+    pub parent: Option<usize>,
+}
+
+struct Reference {
+    span: Span,
+    declaration: usize,
+}
+
+/// Collects diagnostics so they can be sent to the client instead of printed
+struct Collector(Rc<RefCell<Vec<Diagnostic>>>);
+
+impl DiagnosticSink for Collector {
+    fn report(&mut self, diagnostic: Diagnostic, _source: &str) {
+        self.0.borrow_mut().push(diagnostic);
+    }
+}
+
+/// Everything the language server knows about one version of a document
+pub struct Analysis {
+    pub tokens: Vec<Token>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub declarations: Vec<Declaration>,
+    references: Vec<Reference>,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let diagnostics = Rc::new(RefCell::new(Vec::new()));
+        let mut error_handler = SimpleErrorHandler::new();
+        error_handler.set_sink(Box::new(Collector(diagnostics.clone())));
+        error_handler.set_source(source);
+
+        let tokens = Scanner::new(source, &mut error_handler).scan_tokens();
+        let statements = Parser::new(tokens.clone(), &mut error_handler).parse();
+        if !error_handler.had_error {
+            Checker::new(&mut error_handler).check(&statements);
+            Linter::new(&mut error_handler).lint(&statements);
+        }
+
+        let mut resolver = Resolver {
+            declarations: Vec::new(),
+            references: Vec::new(),
+            scopes: vec![Vec::new()],
+        };
+        resolver.resolve(&statements);
+
+        let diagnostics = diagnostics.take();
+        Self {
+            tokens,
+            diagnostics,
+            declarations: resolver.declarations,
+            references: resolver.references,
+        }
+    }
+
+    /// Declaration of the name at `offset`, which may be a use of the name or the
+    /// declaration itself
+    pub fn declaration_at(&self, offset: u32) -> Option<&Declaration> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        self.references
+            .iter()
+            .find(|reference| contains(&reference.span))
+            .map(|reference| &self.declarations[reference.declaration])
+            .or_else(|| {
+                self.declarations
+                    .iter()
+                    .find(|declaration| contains(&declaration.span))
+            })
+    }
+
+    pub fn variants(&self, enum_declaration: &Declaration) -> Vec<&Declaration> {
+        self.declarations
+            .iter()
+            .filter(|declaration| {
+                declaration.parent.is_some_and(|parent| {
+                    std::ptr::eq(&self.declarations[parent], enum_declaration)
+                })
+            })
+            .collect()
+    }
+
+    /// The identifier token touching `offset`, if any
+    pub fn identifier_at(&self, offset: u32) -> Option<&Token> {
+        self.tokens.iter().find(|token| {
+            token.token_type == TokenType::Identifier
+                && token.start <= offset
+                && offset <= token.end
+        })
+    }
+
+    /// Names declared before `offset` in scopes that are still open there, innermost
+    /// first. Works from the tokens rather than the AST so that it still gives useful
+    /// results while the code being typed doesn't parse.
+    pub fn names_in_scope(&self, offset: u32) -> Vec<&Token> {
+        // Each scope remembers whether it belongs to a `for` loop, and if so whether
+        // the loop's body is a block
+        let mut scopes: Vec<(Vec<&Token>, Option<bool>)> = vec![(Vec::new(), None)];
+        let mut depth = 0;
+        let mut loop_headers: Vec<usize> = Vec::new();
+        let tokens: Vec<&Token> = self
+            .tokens
+            .iter()
+            .take_while(|token| token.end < offset)
+            .collect();
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token.token_type {
+                TokenType::Var | TokenType::Enum => {
+                    if let Some(name) = tokens.get(i + 1) {
+                        if name.token_type == TokenType::Identifier {
+                            scopes.last_mut().unwrap().0.push(name);
+                        }
+                    }
+                }
+                TokenType::For => {
+                    scopes.push((Vec::new(), Some(false)));
+                    loop_headers.push(depth);
+                }
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => {
+                    depth = depth.saturating_sub(1);
+                    if loop_headers.last() == Some(&depth) {
+                        loop_headers.pop();
+                        let braced = matches!(tokens.get(i + 1), Some(next) if next.token_type == TokenType::LeftBrace);
+                        scopes.last_mut().unwrap().1 = Some(braced);
+                    }
+                }
+                TokenType::LeftBrace => scopes.push((Vec::new(), None)),
+                TokenType::RightBrace => {
+                    if scopes.len() > 1 {
+                        scopes.pop();
+                    }
+                    while scopes.len() > 1 && scopes.last().unwrap().1 == Some(true) {
+                        scopes.pop();
+                    }
+                }
+                TokenType::Semicolon if depth == 0 => {
+                    while scopes.len() > 1 && scopes.last().unwrap().1 == Some(false) {
+                        scopes.pop();
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut names: Vec<&Token> = Vec::new();
+        for (scope, _) in scopes.iter().rev() {
+            for name in scope.iter().rev() {
+                if !names.iter().any(|seen| seen.lexeme == name.lexeme) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+}
+
+/// Links every use of a name to its declaration, following the same scoping rules as
+/// the interpreter
+struct Resolver {
+    declarations: Vec<Declaration>,
+    references: Vec<Reference>,
+    /// Indices into `declarations`, innermost scope last
+    scopes: Vec<Vec<usize>>,
+}
+
+impl Resolver {
+    fn resolve(&mut self, statements: &[stmt::Stmt]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn visit_enum_stmt(&mut self, stmt: &stmt::Enum) {
+        let parent = self.declare(
+            &stmt.name,
+            DeclarationKind::Enum,
+            format!("enum {}", stmt.name.lexeme),
+        );
+        for variant in &stmt.variants {
+            let detail = match &variant.fields {
+                Some(fields) => {
+                    let names: Vec<&str> =
+                        fields.iter().map(|field| field.lexeme.as_str()).collect();
+                    format!(
+                        "{}.{}({})",
+                        stmt.name.lexeme,
+                        variant.name.lexeme,
+                        names.join(", ")
+                    )
+                }
+                None => format!("{}.{}", stmt.name.lexeme, variant.name.lexeme),
+            };
+            self.declarations.push(Declaration {
+                name: variant.name.lexeme.clone(),
+                kind: DeclarationKind::Variant,
+                span: Span::from(&variant.name),
+                detail,
+                global: self.scopes.len() == 1,
+                parent: Some(parent),
+            });
+        }
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &stmt::ForIn) {
+        self.resolve_expr(&stmt.iterable);
+        self.scopes.push(Vec::new());
+        self.declare(
+            &stmt.name,
+            DeclarationKind::LoopVariable,
+            format!("var {}", stmt.name.lexeme),
+        );
+        self.resolve_stmt(&stmt.body);
+        self.scopes.pop();
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &stmt::Var) {
+        self.resolve_expr(&stmt.initializer);
+        let detail = match &stmt.type_annotation {
+            Some(annotation) => format!("var {}: {}", stmt.name.lexeme, annotation.lexeme),
+            None => format!("var {}", stmt.name.lexeme),
+        };
+        self.declare(&stmt.name, DeclarationKind::Variable, detail);
+    }
+
+    fn resolve_stmt(&mut self, statement: &stmt::Stmt) {
+        match statement {
+            stmt::Stmt::Assert(assert_statement) => {
+                self.resolve_expr(&assert_statement.condition);
+                if let Some(message) = &assert_statement.message {
+                    self.resolve_expr(message);
+                }
+            }
+            stmt::Stmt::Block(block_statement) => {
+                self.scopes.push(Vec::new());
+                self.resolve(&block_statement.statements);
+                self.scopes.pop();
+            }
+            stmt::Stmt::Enum(enum_statement) => self.visit_enum_stmt(enum_statement),
+            stmt::Stmt::Error(_) => (),
+            stmt::Stmt::Expression(expression_statement) => {
+                self.resolve_expr(&expression_statement.expression)
+            }
+            stmt::Stmt::ForIn(for_in_statement) => self.visit_for_in_stmt(for_in_statement),
+            stmt::Stmt::If(if_statement) => {
+                self.resolve_expr(&if_statement.condition);
+                self.resolve_stmt(&if_statement.then_branch);
+                if let Some(else_branch) = &if_statement.else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            stmt::Stmt::Print(print_statement) => self.resolve_expr(&print_statement.expression),
+            stmt::Stmt::Var(var_statement) => self.visit_var_stmt(var_statement),
+            stmt::Stmt::While(while_statement) => {
+                self.resolve_expr(&while_statement.condition);
+                self.resolve_stmt(&while_statement.body);
+            }
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) {
+        self.resolve_expr(&expr.object);
+        // `Enum.Variant` refers to the variant's declaration
+        if let expr::Expr::Variable(variable) = &*expr.object {
+            let parent = self.lookup(&variable.name.lexeme);
+            let variant = self.declarations.iter().position(|declaration| {
+                parent.is_some()
+                    && declaration.parent == parent
+                    && declaration.name == expr.name.lexeme
+            });
+            if let Some(declaration) = variant {
+                self.references.push(Reference {
+                    span: Span::from(&expr.name),
+                    declaration,
+                });
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &expr::Expr) {
+        match expr {
+            expr::Expr::Assign(assign) => {
+                self.resolve_expr(&assign.value);
+                self.reference(&assign.name);
+            }
+            expr::Expr::Binary(binary) => {
+                self.resolve_expr(&binary.left);
+                self.resolve_expr(&binary.right);
+            }
+            expr::Expr::Call(call) => {
+                self.resolve_expr(&call.callee);
+                for argument in &call.arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            expr::Expr::Error(_) => (),
+            expr::Expr::Get(get) => self.visit_get_expr(get),
+            expr::Expr::Grouping(grouping) => self.resolve_expr(&grouping.expression),
+            expr::Expr::Index(index) => {
+                self.resolve_expr(&index.object);
+                self.resolve_expr(&index.index);
+            }
+            expr::Expr::Literal(_) => (),
+            expr::Expr::Logical(logical) => {
+                self.resolve_expr(&logical.left);
+                self.resolve_expr(&logical.right);
+            }
+            expr::Expr::Range(range) => {
+                self.resolve_expr(&range.start);
+                self.resolve_expr(&range.end);
+            }
+            expr::Expr::Unary(unary) => self.resolve_expr(&unary.right),
+            expr::Expr::Variable(variable) => self.reference(&variable.name),
+        }
+    }
+
+    fn declare(&mut self, name: &Token, kind: DeclarationKind, detail: String) -> usize {
+        self.declarations.push(Declaration {
+            name: name.lexeme.clone(),
+            kind,
+            span: Span::from(name),
+            detail,
+            global: self.scopes.len() == 1,
+            parent: None,
+        });
+        let index = self.declarations.len() - 1;
+        self.scopes.last_mut().unwrap().push(index);
+        index
+    }
+
+    fn reference(&mut self, name: &Token) {
+        if let Some(declaration) = self.lookup(&name.lexeme) {
+            self.references.push(Reference {
+                span: Span::from(name),
+                declaration,
+            });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|index| self.declarations[**index].name == name)
+            .copied()
+    }
+}
@@ -0,0 +1,857 @@
+mod analysis;
+use analysis::{Analysis, Declaration, DeclarationKind};
+
+use crate::diagnostic::{Severity, Span};
+use crate::interpreter::environment::Binding;
+use crate::json::Json;
+use crate::scanner::token::Token;
+use crate::scanner::token_type::TokenType;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+
+// JSON-RPC error codes
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Largest message body accepted, far beyond any real Lox document
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+// LSP enums
+const SYNC_FULL: u32 = 1;
+const SEVERITY_ERROR: u32 = 1;
+const SEVERITY_WARNING: u32 = 2;
+const SYMBOL_ENUM: u32 = 10;
+const SYMBOL_VARIABLE: u32 = 13;
+const SYMBOL_ENUM_MEMBER: u32 = 22;
+const COMPLETION_FUNCTION: u32 = 3;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_ENUM: u32 = 13;
+const COMPLETION_ENUM_MEMBER: u32 = 20;
+
+/// Language server speaking LSP over JSON-RPC. Documents are kept in full and
+/// re-analyzed on every request, which is cheap at the size of typical Lox scripts.
+pub struct Server {
+    documents: HashMap<String, String>,
+    /// Natives and prelude globals, available in every document
+    builtins: Vec<Binding>,
+    shutdown_requested: bool,
+}
+
+impl Server {
+    pub fn new(builtins: Vec<Binding>) -> Self {
+        Self {
+            documents: HashMap::new(),
+            builtins,
+            shutdown_requested: false,
+        }
+    }
+
+    /// Serves requests until the client sends `exit` or closes the input. Returns the
+    /// process exit code: 0 if the client asked to shut down first, 1 otherwise.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<i32> {
+        while let Some(body) = read_message(&mut input)? {
+            let message = match body.and_then(|body| Json::parse(&body)) {
+                Ok(message) => message,
+                Err(error) => {
+                    let response = error_response(Json::Null, PARSE_ERROR, &error);
+                    write_message(&mut output, &response)?;
+                    continue;
+                }
+            };
+            let method = match message.get("method").and_then(Json::as_str) {
+                Some(method) => method,
+                // A response to a request we never send
+                None => continue,
+            };
+            if method == "exit" {
+                return Ok(if self.shutdown_requested { 0 } else { 1 });
+            }
+            let params = message.get("params").cloned().unwrap_or(Json::Null);
+            match message.get("id").cloned() {
+                Some(id) => {
+                    let response = match self.request(method, &params) {
+                        Ok(result) => Json::object(vec![
+                            ("jsonrpc", Json::from("2.0")),
+                            ("id", id),
+                            ("result", result),
+                        ]),
+                        Err((code, error)) => error_response(id, code, &error),
+                    };
+                    write_message(&mut output, &response)?;
+                }
+                None => {
+                    for notification in self.notification(method, &params) {
+                        write_message(&mut output, &notification)?;
+                    }
+                }
+            }
+        }
+        Ok(if self.shutdown_requested { 0 } else { 1 })
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+        if self.shutdown_requested {
+            return Err((INVALID_REQUEST, String::from("Server is shutting down.")));
+        }
+        match method {
+            "initialize" => Ok(Server::initialize()),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Json::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/completion" => self.completion(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        }
+    }
+
+    /// Handles a notification, returning any notifications to send back
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let uri = match document.and_then(|document| document.get("uri")) {
+            Some(Json::String(uri)) => uri.clone(),
+            _ => return Vec::new(),
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = document.and_then(|document| document.get("text"));
+                if let Some(text) = text.and_then(Json::as_str) {
+                    self.documents.insert(uri.clone(), String::from(text));
+                }
+            }
+            "textDocument/didChange" => {
+                // Full sync, so the last change holds the whole document
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                if let Some(text) = text {
+                    self.documents.insert(uri.clone(), String::from(text));
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return Vec::new(),
+        }
+        vec![self.publish_diagnostics(&uri)]
+    }
+
+    fn initialize() -> Json {
+        Json::object(vec![
+            (
+                "capabilities",
+                Json::object(vec![
+                    ("textDocumentSync", Json::from(SYNC_FULL)),
+                    ("definitionProvider", Json::from(true)),
+                    ("hoverProvider", Json::from(true)),
+                    ("documentSymbolProvider", Json::from(true)),
+                    (
+                        "completionProvider",
+                        Json::object(vec![(
+                            "triggerCharacters",
+                            Json::from(vec![Json::from(".")]),
+                        )]),
+                    ),
+                ]),
+            ),
+            (
+                "serverInfo",
+                Json::object(vec![
+                    ("name", Json::from("rlox")),
+                    ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+                ]),
+            ),
+        ])
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Json {
+        let diagnostics = match self.documents.get(uri) {
+            Some(text) => {
+                let lines = LineIndex::new(text);
+                Analysis::new(text)
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        Json::object(vec![
+                            ("range", lines.range(diagnostic.span)),
+                            (
+                                "severity",
+                                Json::from(match diagnostic.severity {
+                                    Severity::Error => SEVERITY_ERROR,
+                                    Severity::Warning => SEVERITY_WARNING,
+                                }),
+                            ),
+                            ("code", Json::from(diagnostic.code)),
+                            ("source", Json::from("rlox")),
+                            ("message", Json::from(diagnostic.message.as_str())),
+                        ])
+                    })
+                    .collect()
+            }
+            // Closed documents have their diagnostics cleared
+            None => Vec::new(),
+        };
+        Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from("textDocument/publishDiagnostics")),
+            (
+                "params",
+                Json::object(vec![
+                    ("uri", Json::from(uri)),
+                    ("diagnostics", Json::from(diagnostics)),
+                ]),
+            ),
+        ])
+    }
+
+    fn definition(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (uri, text, offset) = self.position(params)?;
+        let lines = LineIndex::new(text);
+        Ok(match Analysis::new(text).declaration_at(offset) {
+            Some(declaration) => Json::object(vec![
+                ("uri", Json::from(uri)),
+                ("range", lines.range(declaration.span)),
+            ]),
+            None => Json::Null,
+        })
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (_, text, offset) = self.position(params)?;
+        let lines = LineIndex::new(text);
+        let analysis = Analysis::new(text);
+        let token = match analysis.identifier_at(offset) {
+            Some(token) => token,
+            None => return Ok(Json::Null),
+        };
+        let (detail, description) = match analysis.declaration_at(offset) {
+            Some(declaration) => (
+                declaration.detail.clone(),
+                format!(
+                    "{}, declared on line {}",
+                    Server::describe(declaration),
+                    declaration.span.line
+                ),
+            ),
+            None => match self.builtin(&token.lexeme) {
+                Some(builtin) if builtin.callable => {
+                    (builtin.detail.clone(), String::from("built-in function"))
+                }
+                Some(builtin) => (
+                    builtin.detail.clone(),
+                    String::from("global from the prelude"),
+                ),
+                None => return Ok(Json::Null),
+            },
+        };
+        Ok(Json::object(vec![
+            (
+                "contents",
+                Json::object(vec![
+                    ("kind", Json::from("markdown")),
+                    (
+                        "value",
+                        Json::from(format!("```lox\n{}\n```\n{}", detail, description)),
+                    ),
+                ]),
+            ),
+            ("range", lines.range(Span::from(token))),
+        ]))
+    }
+
+    fn document_symbols(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let text = self.document(params)?.1;
+        let lines = LineIndex::new(text);
+        let analysis = Analysis::new(text);
+        let symbol = |declaration: &Declaration, kind: u32, children: Vec<Json>| {
+            let range = lines.range(declaration.span);
+            Json::object(vec![
+                ("name", Json::from(declaration.name.as_str())),
+                ("detail", Json::from(declaration.detail.as_str())),
+                ("kind", Json::from(kind)),
+                ("range", range.clone()),
+                ("selectionRange", range),
+                ("children", Json::from(children)),
+            ])
+        };
+        let symbols = analysis
+            .declarations
+            .iter()
+            .filter(|declaration| declaration.global)
+            .filter_map(|declaration| match declaration.kind {
+                DeclarationKind::Variable => Some(symbol(declaration, SYMBOL_VARIABLE, Vec::new())),
+                DeclarationKind::Enum => {
+                    let variants = analysis
+                        .variants(declaration)
+                        .into_iter()
+                        .map(|variant| symbol(variant, SYMBOL_ENUM_MEMBER, Vec::new()))
+                        .collect();
+                    Some(symbol(declaration, SYMBOL_ENUM, variants))
+                }
+                DeclarationKind::LoopVariable | DeclarationKind::Variant => None,
+            })
+            .collect();
+        Ok(Json::Array(symbols))
+    }
+
+    fn completion(&self, params: &Json) -> Result<Json, (i32, String)> {
+        let (_, text, offset) = self.position(params)?;
+        let analysis = Analysis::new(text);
+        let item = |label: &str, kind: u32, detail: &str| {
+            Json::object(vec![
+                ("label", Json::from(label)),
+                ("kind", Json::from(kind)),
+                ("detail", Json::from(detail)),
+            ])
+        };
+
+        // After `Enum.`, possibly with part of a name typed, offer the enum's variants
+        let before: Vec<&Token> = analysis
+            .tokens
+            .iter()
+            .filter(|token| token.start < offset)
+            .collect();
+        let mut end = before.len();
+        if matches!(before.last(), Some(last) if last.token_type == TokenType::Identifier && last.end >= offset)
+        {
+            end -= 1;
+        }
+        if end >= 2 && before[end - 1].token_type == TokenType::Dot {
+            let object = before[end - 2];
+            // The line being typed usually doesn't parse yet, which leaves the name
+            // unresolved, so fall back to the nearest enum declared before it
+            let declaration = analysis.declaration_at(object.start).or_else(|| {
+                analysis.declarations.iter().rev().find(|declaration| {
+                    declaration.kind == DeclarationKind::Enum
+                        && declaration.name == object.lexeme
+                        && declaration.span.end <= object.start
+                })
+            });
+            let items = match declaration {
+                Some(declaration) if declaration.kind == DeclarationKind::Enum => analysis
+                    .variants(declaration)
+                    .into_iter()
+                    .map(|variant| item(&variant.name, COMPLETION_ENUM_MEMBER, &variant.detail))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            return Ok(Json::Array(items));
+        }
+
+        let mut items = Vec::new();
+        let names = analysis.names_in_scope(offset);
+        for name in &names {
+            let declaration = analysis
+                .declarations
+                .iter()
+                .find(|declaration| declaration.span.start == name.start);
+            let (kind, detail) = match declaration {
+                Some(declaration) if declaration.kind == DeclarationKind::Enum => {
+                    (COMPLETION_ENUM, declaration.detail.clone())
+                }
+                Some(declaration) => (COMPLETION_VARIABLE, declaration.detail.clone()),
+                None => (COMPLETION_VARIABLE, format!("var {}", name.lexeme)),
+            };
+            items.push(item(&name.lexeme, kind, &detail));
+        }
+        for builtin in &self.builtins {
+            if names.iter().all(|name| name.lexeme != builtin.name) {
+                let kind = if builtin.callable {
+                    COMPLETION_FUNCTION
+                } else {
+                    COMPLETION_VARIABLE
+                };
+                items.push(item(&builtin.name, kind, &builtin.detail));
+            }
+        }
+        Ok(Json::Array(items))
+    }
+
+    fn describe(declaration: &Declaration) -> &'static str {
+        match declaration.kind {
+            DeclarationKind::Variable if declaration.global => "global variable",
+            DeclarationKind::Variable => "local variable",
+            DeclarationKind::LoopVariable => "loop variable",
+            DeclarationKind::Enum => "enum",
+            DeclarationKind::Variant => "enum variant",
+        }
+    }
+
+    fn builtin(&self, name: &str) -> Option<&Binding> {
+        self.builtins.iter().find(|builtin| builtin.name == name)
+    }
+
+    /// URI and text of the document named in `params`
+    fn document<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a str), (i32, String)> {
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .ok_or_else(|| (INVALID_PARAMS, String::from("Missing textDocument.uri.")))?;
+        match self.documents.get(uri) {
+            Some(text) => Ok((uri, text)),
+            None => Err((INVALID_PARAMS, format!("Unknown document '{}'.", uri))),
+        }
+    }
+
+    /// URI, text and char offset of the document position in `params`
+    fn position<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a str, u32), (i32, String)> {
+        let (uri, text) = self.document(params)?;
+        let position = params.get("position");
+        let field = |name: &str| {
+            position
+                .and_then(|position| position.get(name))
+                .and_then(Json::as_u32)
+                .ok_or_else(|| (INVALID_PARAMS, format!("Missing position.{}.", name)))
+        };
+        let offset = LineIndex::new(text).offset(field("line")?, field("character")?);
+        Ok((uri, text, offset))
+    }
+}
+
+/// Converts between char offsets, which spans use, and LSP positions, which count
+/// lines from 0 and characters in UTF-16 code units
+struct LineIndex {
+    chars: Vec<char>,
+    /// Offset of the first char of each line
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let mut line_starts = vec![0];
+        for (i, c) in chars.iter().enumerate() {
+            if *c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        Self { chars, line_starts }
+    }
+
+    fn offset(&self, line: u32, character: u32) -> u32 {
+        let start = match self.line_starts.get(line as usize) {
+            Some(start) => *start,
+            None => return self.chars.len() as u32,
+        };
+        let mut offset = start;
+        let mut units = 0;
+        while offset < self.chars.len() && self.chars[offset] != '\n' && units < character {
+            units += self.chars[offset].len_utf16() as u32;
+            offset += 1;
+        }
+        offset as u32
+    }
+
+    fn position(&self, offset: u32) -> Json {
+        let offset = (offset as usize).min(self.chars.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let character: usize = self.chars[self.line_starts[line]..offset]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum();
+        Json::object(vec![
+            ("line", Json::from(line as u32)),
+            ("character", Json::from(character as u32)),
+        ])
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::object(vec![
+            ("start", self.position(span.start)),
+            ("end", self.position(span.end)),
+        ])
+    }
+}
+
+fn error_response(id: Json, code: i32, message: &str) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::from("2.0")),
+        ("id", id),
+        (
+            "error",
+            Json::object(vec![
+                ("code", Json::Number(code as f64)),
+                ("message", Json::from(message)),
+            ]),
+        ),
+    ])
+}
+
+/// Reads one message body framed by a `Content-Length` header, or `None` at the end
+/// of the input. A message that can't be used, e.g. one over `MAX_MESSAGE_LENGTH`, is
+/// skipped and returned as an error to send back to the client.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<String, String>>> {
+    let mut length = None;
+    let mut headers = 0;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if headers > 0 {
+                break;
+            }
+            continue;
+        }
+        headers += 1;
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = match length {
+        Some(length) => length,
+        None => {
+            return Ok(Some(Err(String::from(
+                "Missing or invalid Content-Length.",
+            ))))
+        }
+    };
+    let mut body = Vec::new();
+    if length > MAX_MESSAGE_LENGTH {
+        io::copy(&mut input.take(length as u64), &mut io::sink())?;
+        return Ok(Some(Err(format!(
+            "Message of {} bytes is over the limit of {} bytes.",
+            length, MAX_MESSAGE_LENGTH
+        ))));
+    }
+    input.take(length as u64).read_to_end(&mut body)?;
+    if body.len() < length {
+        // The input ended partway through the body
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8(body).map_err(|_| {
+        String::from("Message body is not valid UTF-8.")
+    })))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::interpreter::environment::Environment;
+    use crate::interpreter::native::{self, FsPolicy};
+
+    const URI: &str = "file:///test.lox";
+    const DOCUMENT: &str = "enum Shape { Circle(r), Square }\\nvar area = 2;\\nprint area;\\nprint len(\\\"ab\\\");\\n";
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    fn request(id: u32, method: &str, params: &str) -> String {
+        frame(&format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+            id, method, params
+        ))
+    }
+
+    fn notification(method: &str, params: &str) -> String {
+        frame(&format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#,
+            method, params
+        ))
+    }
+
+    fn did_open(text: &str) -> String {
+        notification(
+            "textDocument/didOpen",
+            &format!(
+                r#"{{"textDocument":{{"uri":"{}","languageId":"lox","version":1,"text":"{}"}}}}"#,
+                URI, text
+            ),
+        )
+    }
+
+    fn at(line: u32, character: u32) -> String {
+        format!(
+            r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#,
+            URI, line, character
+        )
+    }
+
+    fn shutdown_and_exit() -> String {
+        request(99, "shutdown", "null") + &notification("exit", "null")
+    }
+
+    /// Feeds `transcript` to a server, returning its exit code and every message it sent
+    fn run(transcript: &str) -> (i32, Vec<Json>) {
+        let mut environment = Environment::new();
+        native::define_globals(
+            &mut environment,
+            Rc::new(RefCell::new(FsPolicy::disabled())),
+        );
+        let mut output = Vec::new();
+        let code = Server::new(environment.bindings())
+            .run(transcript.as_bytes(), &mut output)
+            .unwrap();
+
+        let mut reader = output.as_slice();
+        let mut messages = Vec::new();
+        while let Some(body) = read_message(&mut reader).unwrap() {
+            messages.push(Json::parse(&body.unwrap()).unwrap());
+        }
+        (code, messages)
+    }
+
+    /// The response to request `id`
+    fn response(messages: &[Json], id: u32) -> &Json {
+        messages
+            .iter()
+            .find(|message| message.get("id").and_then(Json::as_u32) == Some(id))
+            .unwrap_or_else(|| panic!("no response to request {}", id))
+    }
+
+    fn result(messages: &[Json], id: u32) -> &Json {
+        response(messages, id).get("result").unwrap()
+    }
+
+    fn error_code(message: &Json) -> Option<i32> {
+        match message.get("error")?.get("code")? {
+            Json::Number(code) => Some(*code as i32),
+            _ => None,
+        }
+    }
+
+    fn labels(items: &Json) -> Vec<&str> {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item.get("label").and_then(Json::as_str).unwrap())
+            .collect()
+    }
+
+    fn range(json: &Json) -> (u32, u32, u32, u32) {
+        let range = json.get("range").unwrap();
+        let field = |end: &str, name: &str| range.get(end).unwrap().get(name).unwrap();
+        (
+            field("start", "line").as_u32().unwrap(),
+            field("start", "character").as_u32().unwrap(),
+            field("end", "line").as_u32().unwrap(),
+            field("end", "character").as_u32().unwrap(),
+        )
+    }
+
+    #[test]
+    fn initialize_reports_capabilities() {
+        let (_, messages) = run(&(request(1, "initialize", "{}") + &shutdown_and_exit()));
+        let capabilities = result(&messages, 1).get("capabilities").unwrap();
+        assert_eq!(capabilities.get("textDocumentSync"), Some(&Json::from(1)));
+        assert_eq!(capabilities.get("hoverProvider"), Some(&Json::from(true)));
+        assert_eq!(
+            capabilities.get("definitionProvider"),
+            Some(&Json::from(true))
+        );
+    }
+
+    #[test]
+    fn did_open_publishes_diagnostics() {
+        let (_, messages) = run(&(did_open("var a = 1;\\nprint a +;") + &shutdown_and_exit()));
+        let publish = &messages[0];
+        assert_eq!(
+            publish.get("method").and_then(Json::as_str),
+            Some("textDocument/publishDiagnostics")
+        );
+        let params = publish.get("params").unwrap();
+        assert_eq!(params.get("uri").and_then(Json::as_str), Some(URI));
+        let diagnostics = params.get("diagnostics").and_then(Json::as_array).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].get("code").and_then(Json::as_str),
+            Some("parse")
+        );
+        assert_eq!(diagnostics[0].get("severity"), Some(&Json::from(1)));
+        assert_eq!(range(&diagnostics[0]), (1, 9, 1, 10));
+    }
+
+    #[test]
+    fn did_change_republishes_and_did_close_clears_diagnostics() {
+        let change = notification(
+            "textDocument/didChange",
+            &format!(
+                r#"{{"textDocument":{{"uri":"{}","version":2}},"contentChanges":[{{"text":"print 1;"}}]}}"#,
+                URI
+            ),
+        );
+        let close = notification(
+            "textDocument/didClose",
+            &format!(r#"{{"textDocument":{{"uri":"{}"}}}}"#, URI),
+        );
+        let (_, messages) = run(&(did_open("print ;") + &change + &close + &shutdown_and_exit()));
+        let counts: Vec<usize> = messages[..3]
+            .iter()
+            .map(|message| {
+                let params = message.get("params").unwrap();
+                params
+                    .get("diagnostics")
+                    .and_then(Json::as_array)
+                    .unwrap()
+                    .len()
+            })
+            .collect();
+        assert_eq!(counts, vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn definition_finds_the_declaration() {
+        let transcript = did_open(DOCUMENT)
+            + &request(1, "textDocument/definition", &at(2, 7))
+            + &request(2, "textDocument/definition", &at(3, 7))
+            + &shutdown_and_exit();
+        let (_, messages) = run(&transcript);
+        let location = result(&messages, 1);
+        assert_eq!(location.get("uri").and_then(Json::as_str), Some(URI));
+        assert_eq!(range(location), (1, 4, 1, 8));
+        // Natives aren't declared in the document
+        assert_eq!(result(&messages, 2), &Json::Null);
+    }
+
+    #[test]
+    fn hover_describes_variables_and_builtins() {
+        let transcript = did_open(DOCUMENT)
+            + &request(1, "textDocument/hover", &at(2, 7))
+            + &request(2, "textDocument/hover", &at(3, 7))
+            + &request(3, "textDocument/hover", &at(2, 2))
+            + &shutdown_and_exit();
+        let (_, messages) = run(&transcript);
+        let value = |id| {
+            result(&messages, id)
+                .get("contents")
+                .and_then(|contents| contents.get("value"))
+                .and_then(Json::as_str)
+                .unwrap()
+                .to_string()
+        };
+        assert!(value(1).contains("var area"));
+        assert!(value(1).contains("global variable, declared on line 2"));
+        assert!(value(2).contains("built-in function"));
+        // Keywords have nothing to show
+        assert_eq!(result(&messages, 3), &Json::Null);
+    }
+
+    #[test]
+    fn document_symbols_list_globals_and_variants() {
+        let transcript = did_open(DOCUMENT)
+            + &request(1, "textDocument/documentSymbol", &at(0, 0))
+            + &shutdown_and_exit();
+        let (_, messages) = run(&transcript);
+        let symbols = result(&messages, 1).as_array().unwrap();
+        let names: Vec<&str> = symbols
+            .iter()
+            .map(|symbol| symbol.get("name").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(names, vec!["Shape", "area"]);
+        let variants: Vec<&str> = symbols[0]
+            .get("children")
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .map(|symbol| symbol.get("name").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(variants, vec!["Circle", "Square"]);
+    }
+
+    #[test]
+    fn completion_after_an_enum_offers_its_variants() {
+        let transcript = did_open("enum Shape { Circle(r), Square }\\nShape.\\nShape.Ci")
+            + &request(1, "textDocument/completion", &at(1, 6))
+            + &request(2, "textDocument/completion", &at(2, 8))
+            + &shutdown_and_exit();
+        let (_, messages) = run(&transcript);
+        assert_eq!(labels(result(&messages, 1)), vec!["Circle", "Square"]);
+        assert_eq!(labels(result(&messages, 2)), vec!["Circle", "Square"]);
+    }
+
+    #[test]
+    fn completion_offers_names_in_scope_and_builtins() {
+        let transcript = did_open("var outer = 1;\\n{ var inner = 2; }\\n")
+            + &request(1, "textDocument/completion", &at(2, 0))
+            + &shutdown_and_exit();
+        let (_, messages) = run(&transcript);
+        let labels = labels(result(&messages, 1));
+        assert_eq!(labels[0], "outer");
+        assert!(!labels.contains(&"inner"));
+        assert!(labels.contains(&"len"));
+    }
+
+    #[test]
+    fn exit_code_depends_on_shutdown() {
+        assert_eq!(run(&shutdown_and_exit()).0, 0);
+        assert_eq!(run(&notification("exit", "null")).0, 1);
+        // The input closing without an exit counts the same way
+        assert_eq!(run(&request(1, "shutdown", "null")).0, 0);
+        assert_eq!(run("").0, 1);
+    }
+
+    #[test]
+    fn rejects_unknown_methods_and_requests_after_shutdown() {
+        let transcript = request(1, "workspace/unknown", "{}")
+            + &request(2, "shutdown", "null")
+            + &request(3, "textDocument/hover", &at(0, 0));
+        let (_, messages) = run(&transcript);
+        assert_eq!(error_code(response(&messages, 1)), Some(METHOD_NOT_FOUND));
+        assert_eq!(error_code(response(&messages, 3)), Some(INVALID_REQUEST));
+    }
+
+    #[test]
+    fn rejects_requests_for_unknown_documents() {
+        let (_, messages) = run(&request(1, "textDocument/hover", &at(0, 0)));
+        assert_eq!(error_code(response(&messages, 1)), Some(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn survives_invalid_json() {
+        let transcript = frame("{\"jsonrpc\":") + &shutdown_and_exit();
+        let (code, messages) = run(&transcript);
+        assert_eq!(error_code(&messages[0]), Some(PARSE_ERROR));
+        assert_eq!(messages[0].get("id"), Some(&Json::Null));
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn survives_an_unpaired_surrogate() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"x","params":"\ud800\u0000"}"#;
+        let (code, messages) = run(&(frame(body) + &shutdown_and_exit()));
+        assert_eq!(error_code(&messages[0]), Some(PARSE_ERROR));
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn survives_deeply_nested_json() {
+        let (code, messages) = run(&(frame(&"[".repeat(200_000)) + &shutdown_and_exit()));
+        assert_eq!(error_code(&messages[0]), Some(PARSE_ERROR));
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn survives_an_oversized_content_length() {
+        let (code, messages) = run("Content-Length: 999999999999\r\n\r\n{}");
+        assert_eq!(error_code(&messages[0]), Some(PARSE_ERROR));
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn survives_an_invalid_content_length() {
+        let transcript = String::from("Content-Length: lots\r\n\r\n") + &shutdown_and_exit();
+        let (_, messages) = run(&transcript);
+        assert_eq!(error_code(&messages[0]), Some(PARSE_ERROR));
+    }
+}
@@ -1,14 +1,75 @@
 use std::{env, io, process};
 
+use rlox::diagnostic::JsonSink;
+use rlox::interpreter::native::FsPolicy;
 use rlox::Lox;
+
+fn usage() -> ! {
+    println!("Usage: rlox [--error-format=human|json] [script]");
+    println!("       rlox [--error-format=human|json] check [script]");
+    println!("       rlox [--error-format=human|json] fmt [--check] [script]");
+    println!("       rlox [--error-format=human|json] debug [script]");
+    println!("       rlox lsp");
+    println!("       rlox [--error-format=human|json] --ast [script]");
+    println!("       rlox [--error-format=human|json] --tokens[=json] [script]");
+    process::exit(64);
+}
+
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut json_errors = false;
+    let mut print_ast = false;
+    // `Some(true)` prints the tokens as JSON
+    let mut print_tokens = None;
+    let mut args: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.strip_prefix("--error-format=") {
+            Some("json") => json_errors = true,
+            Some("human") => json_errors = false,
+            Some(_) => usage(),
+            None if arg == "--ast" => print_ast = true,
+            None if arg == "--tokens" => print_tokens = Some(false),
+            None if arg == "--tokens=json" => print_tokens = Some(true),
+            None => args.push(arg),
+        }
+    }
+
     let mut lox = Lox::new();
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        process::exit(64);
-    } else if args.len() == 2 {
-        lox.run_file(&args[1])?
+    lox.set_fs_policy(FsPolicy::working_directory());
+    if json_errors {
+        // Every command that reads a file takes it as the last argument
+        let file = match args.as_slice() {
+            [] => None,
+            [command] if command == "lsp" => None,
+            [.., file] => Some(file.clone()),
+        };
+        lox.set_diagnostic_sink(Box::new(JsonSink::new(file)));
+    }
+    if let Some(json) = print_tokens {
+        match args.as_slice() {
+            [file] => lox.print_tokens(file, json)?,
+            _ => usage(),
+        }
+    } else if print_ast {
+        match args.as_slice() {
+            [file] => lox.print_ast(file)?,
+            _ => usage(),
+        }
+    } else if args.len() == 1 && args[0] == "lsp" {
+        lox.run_language_server()?
+    } else if args.first().map(String::as_str) == Some("fmt") {
+        match &args[1..] {
+            [file] => lox.format_file(file, false)?,
+            [flag, file] if flag == "--check" => lox.format_file(file, true)?,
+            _ => usage(),
+        }
+    } else if args.len() == 2 && args[0] == "check" {
+        lox.check_file(&args[1])?
+    } else if args.len() == 2 && args[0] == "debug" {
+        lox.debug_file(&args[1])?
+    } else if args.len() > 1 {
+        usage();
+    } else if args.len() == 1 {
+        lox.run_file(&args[0])?
     } else {
         lox.run_prompt()?;
     }
@@ -1,5 +1,6 @@
 use super::super::scanner::token;
 use super::super::scanner::token::Token;
+use crate::diagnostic::Span;
 
 pub struct Assign {
     pub name: Token,
@@ -12,12 +13,38 @@ pub struct Binary {
     pub right: Box<Expr>,
 }
 
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+/// Placeholder for an expression that failed to parse
+pub struct Error {
+    pub span: Span,
+}
+
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
 pub struct Grouping {
     pub expression: Box<Expr>,
+    /// Covers the parentheses as well as the inner expression
+    pub span: Span,
+}
+
+pub struct Index {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub index: Box<Expr>,
 }
 
 pub struct Literal {
     pub value: Box<token::Literal>,
+    /// `None` for literals the parser synthesizes, e.g. the implicit `nil` initializer
+    pub span: Option<Span>,
 }
 
 pub struct Logical {
@@ -26,6 +53,12 @@ pub struct Logical {
     pub right: Box<Expr>,
 }
 
+pub struct Range {
+    pub start: Box<Expr>,
+    pub operator: Token,
+    pub end: Box<Expr>,
+}
+
 pub struct Unary {
     pub operator: Token,
     pub right: Box<Expr>,
@@ -38,9 +71,57 @@ pub struct Variable {
 pub enum Expr {
     Assign(Assign),
     Binary(Binary),
+    Call(Call),
+    Error(Error),
+    Get(Get),
     Grouping(Grouping),
+    Index(Index),
     Literal(Literal),
     Logical(Logical),
+    Range(Range),
     Unary(Unary),
     Variable(Variable),
 }
+
+impl Expr {
+    /// Source span covered by the expression, if any of it came from the source
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Assign(assign) => Some(Expr::join(Span::from(&assign.name), assign.value.span())),
+            Expr::Binary(binary) => Expr::outer(&binary.left, &binary.right),
+            Expr::Call(call) => Some(match call.callee.span() {
+                Some(span) => span.to(Span::from(&call.paren)),
+                None => Span::from(&call.paren),
+            }),
+            Expr::Error(error) => Some(error.span),
+            Expr::Get(get) => Some(match get.object.span() {
+                Some(span) => span.to(Span::from(&get.name)),
+                None => Span::from(&get.name),
+            }),
+            Expr::Grouping(grouping) => Some(grouping.span),
+            Expr::Index(index) => Some(match index.object.span() {
+                Some(span) => span.to(Span::from(&index.bracket)),
+                None => Span::from(&index.bracket),
+            }),
+            Expr::Literal(literal) => literal.span,
+            Expr::Logical(logical) => Expr::outer(&logical.left, &logical.right),
+            Expr::Range(range) => Expr::outer(&range.start, &range.end),
+            Expr::Unary(unary) => Some(Expr::join(Span::from(&unary.operator), unary.right.span())),
+            Expr::Variable(variable) => Some(Span::from(&variable.name)),
+        }
+    }
+
+    fn join(start: Span, end: Option<Span>) -> Span {
+        match end {
+            Some(end) => start.to(end),
+            None => start,
+        }
+    }
+
+    fn outer(left: &Expr, right: &Expr) -> Option<Span> {
+        match (left.span(), right.span()) {
+            (Some(left), right) => Some(Expr::join(left, right)),
+            (None, right) => right,
+        }
+    }
+}
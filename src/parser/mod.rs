@@ -1,5 +1,7 @@
 pub mod expr;
+pub mod printer;
 pub mod stmt;
+use crate::diagnostic::Span;
 use crate::error::SimpleErrorHandler;
 use crate::scanner::token;
 
@@ -12,6 +14,9 @@ use super::scanner::token::Token;
 pub struct Parser<'a> {
     tokens: Vec<Token>,
     current: u32,
+    /// Set after an error until the parser recovers, so one mistake isn't reported
+    /// several times as the rest of the statement fails to line up
+    panic_mode: bool,
     error_handler: &'a mut SimpleErrorHandler,
 }
 
@@ -26,37 +31,99 @@ impl<'a> Parser<'a> {
         Self {
             tokens,
             current: 0,
+            panic_mode: false,
             error_handler,
         }
     }
 
+    /// Parses the whole file. Statements and expressions that fail to parse are kept
+    /// as `Error` placeholders, so the tree is always complete. Running a placeholder
+    /// is a runtime error, so check for reported errors before interpreting the tree.
     pub fn parse(&mut self) -> Vec<stmt::Stmt> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            let statement = match self.declaration() {
-                Ok(stmt) => stmt,
-                Err(_) => {
-                    self.synchronize();
-                    continue;
-                }
-            };
-            statements.push(statement);
+            statements.push(self.declaration());
         }
         statements
     }
 
-    fn declaration(&mut self) -> Result<stmt::Stmt> {
-        if self.match_token(vec![TokenType::Var]) {
-            return self.var_declaration();
+    fn declaration(&mut self) -> stmt::Stmt {
+        let first = self.current as usize;
+        let statement = if self.match_token(vec![TokenType::Enum]) {
+            self.enum_declaration()
+        } else if self.match_token(vec![TokenType::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+        let statement = statement.unwrap_or_else(|_| {
+            self.synchronize(first);
+            let span = Span::from(&self.tokens[first]).to(Span::from(&self.previous()));
+            stmt::Stmt::Error(stmt::Error { span })
+        });
+        self.panic_mode = false;
+        statement
+    }
+
+    fn enum_declaration(&mut self) -> Result<stmt::Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect enum name.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before enum body.")?;
+
+        let mut variants: Vec<stmt::Variant> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let variant_name = self.consume(TokenType::Identifier, "Expect variant name.")?;
+            if variants
+                .iter()
+                .any(|variant| variant.name.lexeme == variant_name.lexeme)
+            {
+                // Report without unwinding - the parser is still in a valid state
+                self.error(&variant_name, "Duplicate variant name in enum.");
+            }
+
+            let mut fields = None;
+            if self.match_token(vec![TokenType::LeftParen]) {
+                let mut names: Vec<Token> = Vec::new();
+                if !self.check(TokenType::RightParen) {
+                    loop {
+                        let field = self.consume(TokenType::Identifier, "Expect field name.")?;
+                        if names.iter().any(|name| name.lexeme == field.lexeme) {
+                            self.error(&field, "Duplicate field name in variant.");
+                        }
+                        names.push(field);
+                        if !self.match_token(vec![TokenType::Comma]) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expect ')' after variant fields.")?;
+                fields = Some(names);
+            }
+            variants.push(stmt::Variant {
+                name: variant_name,
+                fields,
+            });
+
+            if !self.match_token(vec![TokenType::Comma]) {
+                break;
+            }
         }
-        self.statement()
+        self.consume(TokenType::RightBrace, "Expect '}' after enum body.")?;
+
+        Ok(stmt::Stmt::Enum(stmt::Enum { name, variants }))
     }
 
     fn var_declaration(&mut self) -> Result<stmt::Stmt> {
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
 
+        let mut type_annotation = None;
+        if self.match_token(vec![TokenType::Colon]) {
+            type_annotation =
+                Some(self.consume(TokenType::Identifier, "Expect type name after ':'.")?);
+        }
+
         let mut initializer = expr::Expr::Literal(expr::Literal {
             value: Box::new(Literal::Nil),
+            span: None,
         });
         if self.match_token(vec![TokenType::Equal]) {
             initializer = self.expression()?;
@@ -68,12 +135,15 @@ impl<'a> Parser<'a> {
         )?;
         Ok(stmt::Stmt::Var(stmt::Var {
             name,
+            type_annotation,
             initializer: Box::new(initializer),
         }))
     }
 
     fn statement(&mut self) -> Result<stmt::Stmt> {
-        if self.match_token(vec![TokenType::For]) {
+        if self.match_token(vec![TokenType::Assert]) {
+            return self.assert_statement();
+        } else if self.match_token(vec![TokenType::For]) {
             return self.for_statement();
         } else if self.match_token(vec![TokenType::If]) {
             return self.if_statement();
@@ -84,14 +154,41 @@ impl<'a> Parser<'a> {
         } else if self.match_token(vec![TokenType::LeftBrace]) {
             return Ok(stmt::Stmt::Block(stmt::Block {
                 statements: self.block()?,
+                line: None,
             }));
         };
         self.expression_statement()
     }
 
+    fn assert_statement(&mut self) -> Result<stmt::Stmt> {
+        let keyword = self.previous();
+        let first = self.current as usize;
+        let condition = self.expression()?;
+        let source = self.source_text(first, self.current as usize);
+
+        let mut message = None;
+        if self.match_token(vec![TokenType::Comma]) {
+            message = Some(Box::new(self.expression()?));
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after assertion.")?;
+
+        Ok(stmt::Stmt::Assert(stmt::Assert {
+            keyword,
+            condition: Box::new(condition),
+            source,
+            message,
+        }))
+    }
+
     fn for_statement(&mut self) -> Result<stmt::Stmt> {
+        let line = self.previous().line;
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
+        if self.check(TokenType::Var) && self.check_ahead(2, TokenType::In) {
+            self.advance();
+            return self.for_in_statement();
+        }
+
         let initializer;
         if self.match_token(vec![TokenType::Semicolon]) {
             initializer = None;
@@ -123,12 +220,14 @@ impl<'a> Parser<'a> {
                         expression: Box::new(inc),
                     }),
                 ],
+                line: None,
             })
         }
 
-        if let None = condition {
+        if condition.is_none() {
             condition = Some(expr::Expr::Literal(expr::Literal {
                 value: Box::new(token::Literal::True),
+                span: None,
             }));
         }
         body = stmt::Stmt::While(stmt::While {
@@ -137,14 +236,30 @@ impl<'a> Parser<'a> {
         });
 
         if let Some(init) = initializer {
+            // Carries the `for` line so the debugger sees the loop as one statement
             body = stmt::Stmt::Block(stmt::Block {
                 statements: vec![init, body],
+                line: Some(line),
             })
         }
 
         Ok(body)
     }
 
+    fn for_in_statement(&mut self) -> Result<stmt::Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect loop variable name.")?;
+        self.consume(TokenType::In, "Expect 'in' after loop variable.")?;
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
+        let body = self.statement()?;
+
+        Ok(stmt::Stmt::ForIn(stmt::ForIn {
+            name,
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+        }))
+    }
+
     fn if_statement(&mut self) -> Result<stmt::Stmt> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
@@ -195,7 +310,7 @@ impl<'a> Parser<'a> {
         let mut statements = Vec::new();
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            statements.push(self.declaration());
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
@@ -273,15 +388,16 @@ impl<'a> Parser<'a> {
     }
 
     fn comparison(&mut self) -> Result<expr::Expr> {
-        let mut expr = self.term()?;
+        let mut expr = self.range()?;
         while self.match_token(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::Is,
         ]) {
             let operator = self.previous();
-            let right = self.term()?;
+            let right = self.range()?;
             expr = expr::Expr::Binary(expr::Binary {
                 left: Box::new(expr),
                 operator,
@@ -291,6 +407,21 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    fn range(&mut self) -> Result<expr::Expr> {
+        let expr = self.term()?;
+
+        if self.match_token(vec![TokenType::DotDot, TokenType::DotDotEqual]) {
+            let operator = self.previous();
+            let end = self.term()?;
+            return Ok(expr::Expr::Range(expr::Range {
+                start: Box::new(expr),
+                operator,
+                end: Box::new(end),
+            }));
+        }
+        Ok(expr)
+    }
+
     fn term(&mut self) -> Result<expr::Expr> {
         let mut expr = self.factor()?;
 
@@ -330,39 +461,103 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             }));
         };
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<expr::Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_token(vec![TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(vec![TokenType::Dot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = expr::Expr::Get(expr::Get {
+                    object: Box::new(expr),
+                    name,
+                });
+            } else if self.match_token(vec![TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = expr::Expr::Index(expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: expr::Expr) -> Result<expr::Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= 255 {
+                    let token = self.peek().clone();
+                    // Report without unwinding - the parser is still in a valid state
+                    self.error(&token, "Can't have more than 255 arguments.");
+                }
+                arguments.push(self.expression()?);
+                if !self.match_token(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(expr::Expr::Call(expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
     }
 
     fn primary(&mut self) -> Result<expr::Expr> {
         if self.match_token(vec![TokenType::False]) {
             return Ok(expr::Expr::Literal(expr::Literal {
                 value: Box::new(Literal::False),
+                span: Some(Span::from(&self.previous())),
             }));
         } else if self.match_token(vec![TokenType::True]) {
             return Ok(expr::Expr::Literal(expr::Literal {
                 value: Box::new(Literal::True),
+                span: Some(Span::from(&self.previous())),
             }));
         } else if self.match_token(vec![TokenType::Nil]) {
             return Ok(expr::Expr::Literal(expr::Literal {
                 value: Box::new(Literal::Nil),
+                span: Some(Span::from(&self.previous())),
             }));
         } else if self.match_token(vec![TokenType::Number, TokenType::String]) {
+            let token = self.previous();
             return Ok(expr::Expr::Literal(expr::Literal {
-                value: self.previous().literal.unwrap(),
+                span: Some(Span::from(&token)),
+                value: token.literal.unwrap(),
             }));
         } else if self.match_token(vec![TokenType::Identifier]) {
             return Ok(expr::Expr::Variable(expr::Variable {
                 name: self.previous(),
             }));
         } else if self.match_token(vec![TokenType::LeftParen]) {
+            let left_paren = self.previous();
             let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            let right_paren =
+                self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
             return Ok(expr::Expr::Grouping(expr::Grouping {
                 expression: Box::new(expr),
+                span: Span::from(&left_paren).to(Span::from(&right_paren)),
             }));
         }
+        // Leave the token for the enclosing statement to fail on or recover from
         let token = self.peek().clone();
-        Err(self.error(&token, "Expect expression"))
+        self.error(&token, "Expect expression");
+        Ok(expr::Expr::Error(expr::Error {
+            span: Span::from(&token),
+        }))
     }
 
     fn consume(&mut self, token_type: TokenType, msg: &str) -> Result<Token> {
@@ -390,15 +585,22 @@ impl<'a> Parser<'a> {
         self.peek().token_type == token_type
     }
 
+    fn check_ahead(&self, distance: usize, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current as usize + distance) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
             self.current += 1
         };
-        return self.previous();
+        self.previous()
     }
 
     fn is_at_end(&self) -> bool {
-        self.peek().token_type == TokenType::EOF
+        self.peek().token_type == TokenType::Eof
     }
 
     fn peek(&self) -> &Token {
@@ -409,30 +611,161 @@ impl<'a> Parser<'a> {
         self.tokens[(self.current - 1) as usize].clone()
     }
 
+    /// Rebuilds the source of `tokens[first..end]` from the token spans,
+    /// collapsing any whitespace or comments between tokens to a single space
+    fn source_text(&self, first: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut previous_end = None;
+        for token in &self.tokens[first..end] {
+            if matches!(previous_end, Some(previous_end) if token.start > previous_end) {
+                text.push(' ');
+            }
+            text.push_str(&token.lexeme);
+            previous_end = Some(token.end);
+        }
+        text
+    }
+
     fn error(&mut self, token: &Token, msg: &str) -> ParserError {
-        self.error_handler.parser_error(token, msg);
+        if !self.panic_mode {
+            self.error_handler.parser_error(token, msg);
+            self.panic_mode = true;
+        }
         ParserError {}
     }
 
-    fn synchronize(&mut self) {
-        self.advance();
+    /// Skips to the next statement boundary after the statement starting at `first`
+    /// failed to parse
+    fn synchronize(&mut self, first: usize) {
+        // Always make progress, or a statement that fails on its first token would loop
+        if self.current as usize == first {
+            self.advance();
+        }
 
         while !self.is_at_end() {
             if self.previous().token_type == TokenType::Semicolon {
                 return;
             };
             match self.peek().token_type {
-                TokenType::Class
+                TokenType::Assert
+                | TokenType::Class
+                | TokenType::Enum
                 | TokenType::Fun
                 | TokenType::Var
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::RightBrace => return,
                 _ => (),
             }
             self.advance();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::printer::AstPrinter;
+    use super::Parser;
+    use crate::diagnostic::{Diagnostic, DiagnosticSink};
+    use crate::error::SimpleErrorHandler;
+    use crate::scanner::Scanner;
+
+    struct Collector(Rc<RefCell<Vec<Diagnostic>>>);
+
+    impl DiagnosticSink for Collector {
+        fn report(&mut self, diagnostic: Diagnostic, _source: &str) {
+            self.0.borrow_mut().push(diagnostic);
+        }
+    }
+
+    /// Parses `source`, returning the printed tree and the messages of any errors
+    fn parse(source: &str) -> (String, Vec<String>) {
+        let diagnostics = Rc::new(RefCell::new(Vec::new()));
+        let mut error_handler = SimpleErrorHandler::new();
+        error_handler.set_sink(Box::new(Collector(diagnostics.clone())));
+        error_handler.set_source(source);
+        let tokens = Scanner::new(source, &mut error_handler).scan_tokens();
+        let statements = Parser::new(tokens, &mut error_handler).parse();
+        let messages = diagnostics
+            .take()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        (AstPrinter::print(&statements), messages)
+    }
+
+    #[test]
+    fn parses_valid_code_without_errors() {
+        let (tree, errors) = parse("var a = 1 + 2 * 3;\nprint a;");
+        assert_eq!(tree, "(var a (+ 1 (* 2 3)))\n(print a)\n");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn keeps_a_missing_operand_as_an_error_expression() {
+        let (tree, errors) = parse("print 1 +;\nprint 2;");
+        assert_eq!(tree, "(print (+ 1 (error)))\n(print 2)\n");
+        assert_eq!(errors, vec!["Expect expression"]);
+    }
+
+    #[test]
+    fn reports_one_error_per_statement() {
+        let (tree, errors) = parse("var = 1 2 3;\nprint 4;");
+        assert_eq!(tree, "(error)\n(print 4)\n");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn reports_errors_in_separate_statements() {
+        let (tree, errors) = parse("var = 1;\nprint 2;\nvar = 3;");
+        assert_eq!(tree, "(error)\n(print 2)\n(error)\n");
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn recovers_at_a_closing_brace() {
+        let (tree, errors) = parse("{ print (1 }\nprint 2;");
+        assert_eq!(tree, "(block (error))\n(print 2)\n");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn recovers_inside_a_block_and_keeps_later_statements() {
+        let (tree, errors) = parse("{ var = 1; print 2; }");
+        assert_eq!(tree, "(block (error) (print 2))\n");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn rejects_duplicate_variant_fields() {
+        let (tree, errors) = parse("enum E { A(x, y, x) }");
+        assert_eq!(tree, "(enum E (A x y x))\n");
+        assert_eq!(errors, vec!["Duplicate field name in variant."]);
+    }
+
+    #[test]
+    fn rejects_duplicate_variant_names() {
+        let (tree, errors) = parse("enum E { A, B(x), A }");
+        assert_eq!(tree, "(enum E A (B x) A)\n");
+        assert_eq!(errors, vec!["Duplicate variant name in enum."]);
+    }
+
+    #[test]
+    fn allows_the_same_field_name_in_different_variants() {
+        let (_, errors) = parse("enum E { A(x), B(x) }");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn reports_a_missing_semicolon_at_the_end() {
+        let (tree, errors) = parse("print 1");
+        assert_eq!(tree, "(error)\n");
+        assert_eq!(errors, vec!["Expect ';' after value."]);
+    }
+}
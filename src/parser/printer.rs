@@ -0,0 +1,164 @@
+use super::{expr, stmt};
+use crate::scanner::token::Literal;
+
+/// Prints the AST as S-expressions, e.g. `(print (+ 1 (* 2 3)))`, one top-level
+/// statement per line. Shows the tree exactly as parsed, including desugared `for` loops.
+pub struct AstPrinter;
+
+impl AstPrinter {
+    pub fn print(statements: &[stmt::Stmt]) -> String {
+        let mut output = String::new();
+        for statement in statements {
+            output.push_str(&AstPrinter::stmt(statement));
+            output.push('\n');
+        }
+        output
+    }
+
+    pub fn expr(expr: &expr::Expr) -> String {
+        match expr {
+            expr::Expr::Assign(assign) => AstPrinter::parenthesize(
+                "=",
+                &[assign.name.lexeme.clone(), AstPrinter::expr(&assign.value)],
+            ),
+            expr::Expr::Binary(binary) => AstPrinter::parenthesize(
+                &binary.operator.lexeme,
+                &[
+                    AstPrinter::expr(&binary.left),
+                    AstPrinter::expr(&binary.right),
+                ],
+            ),
+            expr::Expr::Call(call) => {
+                let mut parts = vec![AstPrinter::expr(&call.callee)];
+                parts.extend(call.arguments.iter().map(AstPrinter::expr));
+                AstPrinter::parenthesize("call", &parts)
+            }
+            expr::Expr::Error(_) => String::from("(error)"),
+            expr::Expr::Get(get) => AstPrinter::parenthesize(
+                ".",
+                &[AstPrinter::expr(&get.object), get.name.lexeme.clone()],
+            ),
+            expr::Expr::Grouping(grouping) => {
+                AstPrinter::parenthesize("group", &[AstPrinter::expr(&grouping.expression)])
+            }
+            expr::Expr::Index(index) => AstPrinter::parenthesize(
+                "index",
+                &[
+                    AstPrinter::expr(&index.object),
+                    AstPrinter::expr(&index.index),
+                ],
+            ),
+            expr::Expr::Literal(literal) => match &*literal.value {
+                Literal::Number(num) => num.to_string(),
+                Literal::String(string) => format!("{:?}", string),
+                Literal::True => String::from("true"),
+                Literal::False => String::from("false"),
+                Literal::Nil => String::from("nil"),
+            },
+            expr::Expr::Logical(logical) => AstPrinter::parenthesize(
+                &logical.operator.lexeme,
+                &[
+                    AstPrinter::expr(&logical.left),
+                    AstPrinter::expr(&logical.right),
+                ],
+            ),
+            expr::Expr::Range(range) => AstPrinter::parenthesize(
+                &range.operator.lexeme,
+                &[AstPrinter::expr(&range.start), AstPrinter::expr(&range.end)],
+            ),
+            expr::Expr::Unary(unary) => {
+                AstPrinter::parenthesize(&unary.operator.lexeme, &[AstPrinter::expr(&unary.right)])
+            }
+            expr::Expr::Variable(variable) => variable.name.lexeme.clone(),
+        }
+    }
+
+    pub fn stmt(statement: &stmt::Stmt) -> String {
+        match statement {
+            stmt::Stmt::Assert(assert_statement) => {
+                let mut parts = vec![AstPrinter::expr(&assert_statement.condition)];
+                if let Some(message) = &assert_statement.message {
+                    parts.push(AstPrinter::expr(message));
+                }
+                AstPrinter::parenthesize("assert", &parts)
+            }
+            stmt::Stmt::Block(block_statement) => {
+                let parts: Vec<String> = block_statement
+                    .statements
+                    .iter()
+                    .map(AstPrinter::stmt)
+                    .collect();
+                AstPrinter::parenthesize("block", &parts)
+            }
+            stmt::Stmt::Enum(enum_statement) => {
+                let mut parts = vec![enum_statement.name.lexeme.clone()];
+                for variant in &enum_statement.variants {
+                    parts.push(match &variant.fields {
+                        Some(fields) => {
+                            let names: Vec<String> =
+                                fields.iter().map(|field| field.lexeme.clone()).collect();
+                            AstPrinter::parenthesize(&variant.name.lexeme, &names)
+                        }
+                        None => variant.name.lexeme.clone(),
+                    });
+                }
+                AstPrinter::parenthesize("enum", &parts)
+            }
+            stmt::Stmt::Error(_) => String::from("(error)"),
+            stmt::Stmt::Expression(expression_statement) => AstPrinter::parenthesize(
+                "expr",
+                &[AstPrinter::expr(&expression_statement.expression)],
+            ),
+            stmt::Stmt::ForIn(for_in_statement) => AstPrinter::parenthesize(
+                "for-in",
+                &[
+                    for_in_statement.name.lexeme.clone(),
+                    AstPrinter::expr(&for_in_statement.iterable),
+                    AstPrinter::stmt(&for_in_statement.body),
+                ],
+            ),
+            stmt::Stmt::If(if_statement) => {
+                let mut parts = vec![
+                    AstPrinter::expr(&if_statement.condition),
+                    AstPrinter::stmt(&if_statement.then_branch),
+                ];
+                if let Some(else_branch) = &if_statement.else_branch {
+                    parts.push(AstPrinter::stmt(else_branch));
+                }
+                AstPrinter::parenthesize("if", &parts)
+            }
+            stmt::Stmt::Print(print_statement) => {
+                AstPrinter::parenthesize("print", &[AstPrinter::expr(&print_statement.expression)])
+            }
+            stmt::Stmt::Var(var_statement) => {
+                let name = match &var_statement.type_annotation {
+                    Some(annotation) => {
+                        format!("{}: {}", var_statement.name.lexeme, annotation.lexeme)
+                    }
+                    None => var_statement.name.lexeme.clone(),
+                };
+                AstPrinter::parenthesize(
+                    "var",
+                    &[name, AstPrinter::expr(&var_statement.initializer)],
+                )
+            }
+            stmt::Stmt::While(while_statement) => AstPrinter::parenthesize(
+                "while",
+                &[
+                    AstPrinter::expr(&while_statement.condition),
+                    AstPrinter::stmt(&while_statement.body),
+                ],
+            ),
+        }
+    }
+
+    fn parenthesize(name: &str, parts: &[String]) -> String {
+        let mut output = format!("({}", name);
+        for part in parts {
+            output.push(' ');
+            output.push_str(part);
+        }
+        output.push(')');
+        output
+    }
+}
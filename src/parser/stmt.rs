@@ -1,13 +1,47 @@
-use crate::{parser::expr::Expr, scanner::token::Token};
+use crate::{diagnostic::Span, parser::expr::Expr, scanner::token::Token};
+
+pub struct Assert {
+    pub keyword: Token,
+    pub condition: Box<Expr>,
+    /// Source text of the condition, used in the failure message
+    pub source: String,
+    pub message: Option<Box<Expr>>,
+}
 
 pub struct Block {
     pub statements: Vec<Stmt>,
+    /// Line of the `for` loop this block was desugared from. Blocks written as
+    /// braces have none, so the debugger steps straight into them.
+    pub line: Option<u32>,
+}
+
+pub struct Enum {
+    pub name: Token,
+    pub variants: Vec<Variant>,
+}
+
+/// `fields` is `None` for unit variants declared without parentheses
+pub struct Variant {
+    pub name: Token,
+    pub fields: Option<Vec<Token>>,
+}
+
+/// Placeholder for a statement that failed to parse, covering the tokens skipped
+/// while recovering
+pub struct Error {
+    pub span: Span,
 }
 
 pub struct Expression {
     pub expression: Box<Expr>,
 }
 
+pub struct ForIn {
+    pub name: Token,
+    pub iterable: Box<Expr>,
+    pub body: Box<Stmt>,
+}
+
 pub struct If {
     pub condition: Box<Expr>,
     pub then_branch: Box<Stmt>,
@@ -20,6 +54,7 @@ pub struct Print {
 
 pub struct Var {
     pub name: Token,
+    pub type_annotation: Option<Token>,
     pub initializer: Box<Expr>,
 }
 
@@ -29,10 +64,35 @@ pub struct While {
 }
 
 pub enum Stmt {
+    Assert(Assert),
     Block(Block),
+    Enum(Enum),
+    Error(Error),
     Expression(Expression),
+    ForIn(ForIn),
     If(If),
     Print(Print),
     Var(Var),
     While(While),
 }
+
+impl Stmt {
+    /// Line the statement starts on, as near as the AST records it. `None` for blocks
+    /// and for conditions the parser synthesized, e.g. the `true` in `for (;;)`.
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Stmt::Assert(assert_statement) => Some(assert_statement.keyword.line),
+            Stmt::Block(block_statement) => block_statement.line,
+            Stmt::Enum(enum_statement) => Some(enum_statement.name.line),
+            Stmt::Error(error) => Some(error.span.line),
+            Stmt::Expression(expression_statement) => {
+                expression_statement.expression.span().map(|span| span.line)
+            }
+            Stmt::ForIn(for_in_statement) => Some(for_in_statement.name.line),
+            Stmt::If(if_statement) => if_statement.condition.span().map(|span| span.line),
+            Stmt::Print(print_statement) => print_statement.expression.span().map(|span| span.line),
+            Stmt::Var(var_statement) => Some(var_statement.name.line),
+            Stmt::While(while_statement) => while_statement.condition.span().map(|span| span.line),
+        }
+    }
+}
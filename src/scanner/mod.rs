@@ -1,6 +1,7 @@
 pub mod token;
 pub mod token_type;
 use super::SimpleErrorHandler;
+use crate::diagnostic::Span;
 use std::mem;
 use token::Literal;
 use token::Token;
@@ -12,6 +13,13 @@ pub struct Scanner<'a> {
     start: u32,
     current: u32,
     line: u32,
+    /// Offset of the first char of the current line
+    line_start: u32,
+    /// Line and column of `start`, captured before a token can span several lines
+    start_line: u32,
+    start_column: u32,
+    /// Emit `Comment` tokens instead of discarding comments
+    keep_comments: bool,
     error_handler: &'a mut SimpleErrorHandler,
 }
 
@@ -23,22 +31,41 @@ impl<'a> Scanner<'a> {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            keep_comments: false,
             error_handler,
         }
     }
 
+    /// Creates a scanner that keeps comments as `Comment` tokens, for tools that need to
+    /// reproduce the source such as the formatter. Whitespace can be recovered from the
+    /// token positions. The parser doesn't accept `Comment` tokens.
+    pub fn with_comments(source: &str, error_handler: &'a mut SimpleErrorHandler) -> Self {
+        Self {
+            keep_comments: true,
+            ..Self::new(source, error_handler)
+        }
+    }
+
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.current - self.line_start + 1;
             self.scan_token();
         }
         self.tokens.push(Token::new(
-            TokenType::EOF,
+            TokenType::Eof,
             String::from(""),
             None,
             self.line,
+            self.current - self.line_start + 1,
+            self.current,
+            self.current,
         ));
-        return mem::take(&mut self.tokens);
+        mem::take(&mut self.tokens)
     }
 
     fn is_at_end(&self) -> bool {
@@ -52,8 +79,21 @@ impl<'a> Scanner<'a> {
             ')' => Some(TokenType::RightParen),
             '{' => Some(TokenType::LeftBrace),
             '}' => Some(TokenType::RightBrace),
+            '[' => Some(TokenType::LeftBracket),
+            ']' => Some(TokenType::RightBracket),
+            ':' => Some(TokenType::Colon),
             ',' => Some(TokenType::Comma),
-            '.' => Some(TokenType::Dot),
+            '.' => {
+                if self.match_char(&'.') {
+                    if self.match_char(&'=') {
+                        Some(TokenType::DotDotEqual)
+                    } else {
+                        Some(TokenType::DotDot)
+                    }
+                } else {
+                    Some(TokenType::Dot)
+                }
+            }
             '-' => Some(TokenType::Minus),
             '+' => Some(TokenType::Plus),
             ';' => Some(TokenType::Semicolon),
@@ -91,7 +131,11 @@ impl<'a> Scanner<'a> {
                     while self.peek() != &'\n' && !self.is_at_end() {
                         self.advance();
                     }
-                    None
+                    if self.keep_comments {
+                        Some(TokenType::Comment)
+                    } else {
+                        None
+                    }
                 } else {
                     Some(TokenType::Slash)
                 }
@@ -99,24 +143,25 @@ impl<'a> Scanner<'a> {
             ' ' | '\r' | '\t' => None,
             '\n' => {
                 self.line += 1;
+                self.line_start = self.current;
                 None
             }
             '"' => {
                 // String literal - returns None as it uses the add_token_with_literal method
                 while self.peek() != &'"' && !self.is_at_end() {
-                    if self.peek() == &'\n' {
-                        self.line += 1
+                    if self.advance() == &'\n' {
+                        self.line += 1;
+                        self.line_start = self.current;
                     };
-                    self.advance();
                 }
 
                 if self.is_at_end() {
-                    self.error_handler.error(self.line, "Unterminated string.");
+                    self.error("Unterminated string.");
                 } else {
                     self.advance();
                     let value: String = self.source
                         [(self.start + 1) as usize..(self.current - 1) as usize]
-                        .into_iter()
+                        .iter()
                         .collect();
                     self.add_token_with_literal(
                         TokenType::String,
@@ -125,21 +170,21 @@ impl<'a> Scanner<'a> {
                 };
                 None
             }
-            c if c.is_digit(10) => {
+            c if c.is_ascii_digit() => {
                 // Numeric literal
-                while self.peek().is_digit(10) {
+                while self.peek().is_ascii_digit() {
                     self.advance();
                 }
 
                 // Look for a fractional part
-                if self.peek() == &'.' && self.peek_next().is_digit(10) {
+                if self.peek() == &'.' && self.peek_next().is_ascii_digit() {
                     self.advance();
-                    while self.peek().is_digit(10) {
+                    while self.peek().is_ascii_digit() {
                         self.advance();
                     }
                 };
                 let num_str: String = self.source[self.start as usize..self.current as usize]
-                    .into_iter()
+                    .iter()
                     .collect();
                 let num: f64 = num_str.parse().unwrap();
                 self.add_token_with_literal(
@@ -149,11 +194,11 @@ impl<'a> Scanner<'a> {
                 None
             }
             c if c.is_alphabetic() || c == &'_' => {
-                while self.peek().is_alphabetic() || self.peek().is_digit(10) {
+                while self.peek().is_alphabetic() || self.peek().is_ascii_digit() {
                     self.advance();
                 }
                 let text: String = self.source[self.start as usize..self.current as usize]
-                    .into_iter()
+                    .iter()
                     .collect();
                 match Scanner::keywords(&text) {
                     Some(t) => Some(t),
@@ -161,16 +206,25 @@ impl<'a> Scanner<'a> {
                 }
             }
             _ => {
-                self.error_handler.error(self.line, "Unexpected character");
+                self.error("Unexpected character");
                 None
             }
         };
-        match token_type {
-            Some(t) => self.add_token(t),
-            None => (),
+        if let Some(t) = token_type {
+            self.add_token(t)
         };
     }
 
+    fn error(&mut self, message: &str) {
+        let span = Span {
+            line: self.start_line,
+            column: self.start_column,
+            start: self.start,
+            end: self.current,
+        };
+        self.error_handler.error(span, message);
+    }
+
     fn peek(&self) -> &char {
         if self.is_at_end() {
             return &'\0';
@@ -182,7 +236,7 @@ impl<'a> Scanner<'a> {
         if self.current + 1 >= self.source.len() as u32 {
             return &'\0';
         };
-        return &self.source[(self.current + 1) as usize];
+        &self.source[(self.current + 1) as usize]
     }
 
     fn match_char(&mut self, expected: &char) -> bool {
@@ -203,11 +257,17 @@ impl<'a> Scanner<'a> {
 
     fn add_token_with_literal(&mut self, token_type: TokenType, literal: Option<Box<Literal>>) {
         let text: String = self.source[self.start as usize..self.current as usize]
-            .to_owned()
-            .into_iter()
+            .iter()
             .collect();
-        self.tokens
-            .push(Token::new(token_type, text, literal, self.line));
+        self.tokens.push(Token::new(
+            token_type,
+            text,
+            literal,
+            self.start_line,
+            self.start_column,
+            self.start,
+            self.current,
+        ));
     }
 
     fn advance(&mut self) -> &char {
@@ -219,12 +279,16 @@ impl<'a> Scanner<'a> {
     fn keywords(kw: &str) -> Option<TokenType> {
         match kw {
             "and" => Some(TokenType::And),
+            "assert" => Some(TokenType::Assert),
             "class" => Some(TokenType::Class),
             "else" => Some(TokenType::Else),
+            "enum" => Some(TokenType::Enum),
             "false" => Some(TokenType::False),
             "for" => Some(TokenType::For),
             "fun" => Some(TokenType::Fun),
             "if" => Some(TokenType::If),
+            "in" => Some(TokenType::In),
+            "is" => Some(TokenType::Is),
             "nil" => Some(TokenType::Nil),
             "or" => Some(TokenType::Or),
             "print" => Some(TokenType::Print),
@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Display};
 
 use super::token_type::TokenType;
+use crate::json;
 
 #[derive(Clone)]
 pub struct Token {
@@ -8,6 +9,11 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<Box<Literal>>,
     pub line: u32,
+    /// 1-based position of the first char of the lexeme within its line
+    pub column: u32,
+    /// Offsets of the lexeme in the source, counted in chars
+    pub start: u32,
+    pub end: u32,
 }
 
 impl Token {
@@ -16,12 +22,62 @@ impl Token {
         lexeme: String,
         literal: Option<Box<Literal>>,
         line: u32,
+        column: u32,
+        start: u32,
+        end: u32,
     ) -> Self {
         Self {
             token_type,
             lexeme,
             literal,
             line,
+            column,
+            start,
+            end,
+        }
+    }
+}
+
+impl Token {
+    /// One-line JSON object with the same fields as the `Display` format, e.g.
+    /// `{"type":"Number","lexeme":"1.50","literal":1.5,"line":1,"column":9}`.
+    /// `literal` is `null` for tokens without one.
+    pub fn to_json(&self) -> String {
+        let literal = match self.literal.as_deref() {
+            Some(Literal::Number(num)) => num.to_string(),
+            Some(Literal::String(string)) => json::quote(string),
+            Some(Literal::True) => String::from("true"),
+            Some(Literal::False) => String::from("false"),
+            Some(Literal::Nil) | None => String::from("null"),
+        };
+        format!(
+            "{{\"type\":{},\"lexeme\":{},\"literal\":{},\"line\":{},\"column\":{}}}",
+            json::quote(&format!("{:?}", self.token_type)),
+            json::quote(&self.lexeme),
+            literal,
+            self.line,
+            self.column
+        )
+    }
+}
+
+/// Stable format used by `rlox --tokens`: `line:column Type "lexeme"`, followed by the
+/// literal for numbers and strings, e.g. `1:9 String "\"hi\"" "hi"`. Lexemes and string
+/// literals are JSON-quoted so every token fits on one line.
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} {:?} {}",
+            self.line,
+            self.column,
+            self.token_type,
+            json::quote(&self.lexeme)
+        )?;
+        match self.literal.as_deref() {
+            Some(Literal::Number(num)) => write!(f, " {}", num),
+            Some(Literal::String(string)) => write!(f, " {}", json::quote(string)),
+            _ => Ok(()),
         }
     }
 }
@@ -1,10 +1,13 @@
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     // Single-character tokens
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -22,6 +25,8 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    DotDot,
+    DotDotEqual,
 
     // Literals
     Identifier,
@@ -30,12 +35,16 @@ pub enum TokenType {
 
     // Keywords
     And,
+    Assert,
     Class,
     Else,
+    Enum,
     False,
     Fun,
     For,
     If,
+    In,
+    Is,
     Nil,
     Or,
     Print,
@@ -46,5 +55,7 @@ pub enum TokenType {
     Var,
     While,
 
-    EOF,
+    /// Only produced by `Scanner::with_comments`
+    Comment,
+    Eof,
 }